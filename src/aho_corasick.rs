@@ -0,0 +1,474 @@
+use crate::queue::Queue;
+use std::cmp::Reverse;
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    Overlapping,     // every occurrence of every pattern
+    LeftmostLongest, // no overlaps, the match starting first wins and then the longest one
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub pattern: usize, // index into the patterns
+    pub start: usize,
+    pub end: usize, // exclusive, byte offsets like everything else here
+}
+
+struct Node {
+    children: Vec<(u8, usize)>, // sorted by byte
+    fail: usize,                // longest proper suffix that's also in the trie
+    depth: usize,
+    outputs: Vec<usize>, // patterns ending here, including the ones reached by fail links
+}
+
+impl Node {
+    fn new(depth: usize) -> Node {
+        Node {
+            children: Vec::new(),
+            fail: ROOT,
+            depth,
+            outputs: Vec::new(),
+        }
+    }
+
+    fn child(&self, b: u8) -> Option<usize> {
+        self.children
+            .binary_search_by_key(&b, |&(c, _)| c)
+            .ok()
+            .map(|i| self.children[i].1)
+    }
+}
+
+// A trie of all the patterns with failure links, so the haystack is read once no matter
+// how many patterns there are: O(n + patterns length + number of matches).
+pub struct AhoCorasick {
+    nodes: Vec<Node>,
+    lengths: Vec<usize>,
+    kind: MatchKind,
+    ignore_case: bool, // ASCII only
+}
+
+impl AhoCorasick {
+    pub fn new<P: AsRef<[u8]>>(patterns: &[P], kind: MatchKind, ignore_case: bool) -> AhoCorasick {
+        let mut ac = AhoCorasick {
+            nodes: vec![Node::new(0)],
+            lengths: Vec::with_capacity(patterns.len()),
+            kind,
+            ignore_case,
+        };
+
+        for (idx, pattern) in patterns.iter().enumerate() {
+            ac.insert(idx, pattern.as_ref());
+        }
+        ac.link();
+
+        ac
+    }
+
+    fn insert(&mut self, idx: usize, pattern: &[u8]) {
+        if pattern.is_empty() {
+            panic!("Patterns can't be empty");
+        }
+
+        let mut node = ROOT;
+        for &b in pattern.iter() {
+            let b = self.fold(b);
+            node = match self.nodes[node].child(b) {
+                Some(child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new(self.nodes[node].depth + 1));
+
+                    let children = &mut self.nodes[node].children;
+                    let at = children.partition_point(|&(c, _)| c < b);
+                    children.insert(at, (b, child));
+                    child
+                }
+            };
+        }
+
+        self.nodes[node].outputs.push(idx);
+        self.lengths.push(pattern.len());
+    }
+
+    // Breadth first, so the fail link of every node is done before its children need it
+    fn link(&mut self) {
+        let mut queue = Queue::new();
+        for &(_, child) in self.nodes[ROOT].children.iter() {
+            queue.enqueue(child);
+        }
+
+        while let Some(node) = queue.deque() {
+            for i in 0..self.nodes[node].children.len() {
+                let (b, child) = self.nodes[node].children[i];
+                queue.enqueue(child);
+
+                let fail = self.step(self.nodes[node].fail, b);
+                self.nodes[child].fail = fail;
+
+                // Longer ones first, the fail node's outputs are all suffixes
+                let inherited = self.nodes[fail].outputs.clone();
+                self.nodes[child].outputs.extend(inherited);
+            }
+        }
+    }
+
+    fn fold(&self, b: u8) -> u8 {
+        if self.ignore_case {
+            b.to_ascii_lowercase()
+        } else {
+            b
+        }
+    }
+
+    // Follows fail links until there's a way forward with b, b already folded
+    fn step(&self, mut node: usize, b: u8) -> usize {
+        loop {
+            if let Some(child) = self.nodes[node].child(b) {
+                return child;
+            }
+            if node == ROOT {
+                return ROOT;
+            }
+            node = self.nodes[node].fail;
+        }
+    }
+
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<Match> {
+        let mut stream = self.stream();
+        let mut found = stream.feed(haystack);
+        found.extend(stream.finish());
+        found
+    }
+
+    pub fn stream(&self) -> Stream<'_> {
+        Stream {
+            ac: self,
+            state: ROOT,
+            pos: 0,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            best: None,
+        }
+    }
+}
+
+// Matching over input that comes in pieces, a match can span any number of them. Offsets
+// count from the start of the first chunk. Leftmost-longest has to hold on to the bytes
+// of a match it might still replace with a longer one, never more than the longest pattern.
+pub struct Stream<'a> {
+    ac: &'a AhoCorasick,
+    state: usize,
+    pos: usize, // offset of the next byte to look at
+    // Leftmost-longest only: bytes from buffer_start on, in case they have to be read again
+    buffer: Vec<u8>,
+    buffer_start: usize,
+    best: Option<Match>,
+}
+
+impl Stream<'_> {
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Match> {
+        let mut found = Vec::new();
+
+        match self.ac.kind {
+            MatchKind::Overlapping => {
+                for &b in chunk.iter() {
+                    self.overlapping(b, &mut found);
+                }
+            }
+            MatchKind::LeftmostLongest => {
+                self.buffer.extend_from_slice(chunk);
+                self.leftmost_longest(&mut found);
+            }
+        }
+
+        found
+    }
+
+    // Whatever was still waiting to see if a longer match comes along
+    pub fn finish(mut self) -> Vec<Match> {
+        let mut found = Vec::new();
+
+        while let Some(best) = self.best.take() {
+            found.push(best);
+            self.restart(best.end);
+            self.leftmost_longest(&mut found);
+        }
+
+        found
+    }
+
+    fn overlapping(&mut self, b: u8, found: &mut Vec<Match>) {
+        let ac = self.ac;
+        self.state = ac.step(self.state, ac.fold(b));
+        self.pos += 1;
+
+        for &pattern in ac.nodes[self.state].outputs.iter() {
+            found.push(Match {
+                pattern,
+                start: self.pos - ac.lengths[pattern],
+                end: self.pos,
+            });
+        }
+    }
+
+    fn leftmost_longest(&mut self, found: &mut Vec<Match>) {
+        let ac = self.ac;
+
+        while self.pos < self.buffer_start + self.buffer.len() {
+            let b = self.buffer[self.pos - self.buffer_start];
+            self.state = ac.step(self.state, ac.fold(b));
+            self.pos += 1;
+
+            for &pattern in ac.nodes[self.state].outputs.iter() {
+                let candidate = Match {
+                    pattern,
+                    start: self.pos - ac.lengths[pattern],
+                    end: self.pos,
+                };
+                if better(candidate, self.best) {
+                    self.best = Some(candidate);
+                }
+            }
+
+            // Anything still to come starts at or after this
+            let earliest = self.pos - ac.nodes[self.state].depth;
+            match self.best {
+                Some(best) if best.start < earliest => {
+                    // Nothing can start before it anymore, so it's final. The search
+                    // starts over right after it.
+                    found.push(best);
+                    self.best = None;
+                    self.restart(best.end);
+                }
+                // Only what comes after it might have to be read again
+                Some(best) => self.trim(best.end),
+                None => self.trim(earliest),
+            }
+        }
+    }
+
+    fn restart(&mut self, at: usize) {
+        self.state = ROOT;
+        self.pos = at;
+        self.trim(at);
+    }
+
+    // Drops the buffered bytes before `at`, they won't be looked at again
+    fn trim(&mut self, at: usize) {
+        let drop = at - self.buffer_start;
+        if drop > 0 {
+            self.buffer.drain(..drop);
+            self.buffer_start = at;
+        }
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+// Leftmost first, then longest, then the lowest pattern index
+fn better(candidate: Match, best: Option<Match>) -> bool {
+    let Some(best) = best else {
+        return true;
+    };
+
+    let key = |m: Match| (m.start, Reverse(m.end - m.start), m.pattern);
+    key(candidate) < key(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    fn brute_overlapping(haystack: &[u8], patterns: &[Vec<u8>], ignore_case: bool) -> Vec<Match> {
+        let eq = |a: &[u8], b: &[u8]| {
+            if ignore_case {
+                a.eq_ignore_ascii_case(b)
+            } else {
+                a == b
+            }
+        };
+
+        let mut found = Vec::new();
+        for end in 1..=haystack.len() {
+            let mut here: Vec<Match> = patterns
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.len() <= end && eq(&haystack[end - p.len()..end], p))
+                .map(|(pattern, p)| Match {
+                    pattern,
+                    start: end - p.len(),
+                    end,
+                })
+                .collect();
+            here.sort_by_key(|m| (m.start, m.pattern));
+            found.extend(here);
+        }
+        found
+    }
+
+    fn brute_leftmost_longest(haystack: &[u8], patterns: &[Vec<u8>]) -> Vec<Match> {
+        let mut found = Vec::new();
+        let mut pos = 0;
+
+        while pos < haystack.len() {
+            let best = (pos..haystack.len()).find_map(|start| {
+                patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| haystack[start..].starts_with(p))
+                    .min_by_key(|(idx, p)| (usize::MAX - p.len(), *idx))
+                    .map(|(pattern, p)| Match {
+                        pattern,
+                        start,
+                        end: start + p.len(),
+                    })
+            });
+
+            match best {
+                Some(m) => {
+                    found.push(m);
+                    pos = m.end;
+                }
+                None => break,
+            }
+        }
+
+        found
+    }
+
+    fn random(rng: &mut XorShift, len: usize) -> Vec<u8> {
+        (0..len).map(|_| b'a' + rng.range(0, 3) as u8).collect()
+    }
+
+    #[test]
+    fn test() {
+        let patterns = ["he", "she", "his", "hers"];
+        let ac = AhoCorasick::new(&patterns, MatchKind::Overlapping, false);
+        let found: Vec<(usize, usize)> = ac
+            .find_all(b"ushers")
+            .iter()
+            .map(|m| (m.pattern, m.start))
+            .collect();
+        assert_eq!(found, [(1, 1), (0, 2), (3, 2)]);
+
+        let ac = AhoCorasick::new(&patterns, MatchKind::LeftmostLongest, false);
+        let found: Vec<usize> = ac.find_all(b"ushers").iter().map(|m| m.pattern).collect();
+        assert_eq!(found, [1]);
+
+        let ac = AhoCorasick::new(&["abcd", "b", "bcd"], MatchKind::LeftmostLongest, false);
+        let found: Vec<usize> = ac.find_all(b"xabcdxbc").iter().map(|m| m.pattern).collect();
+        assert_eq!(found, [0, 1]);
+    }
+
+    #[test]
+    fn ignore_case() {
+        let ac = AhoCorasick::new(&["Error", "WARN"], MatchKind::Overlapping, true);
+        let found: Vec<usize> = ac
+            .find_all(b"ERROR: warn, error! Warning")
+            .iter()
+            .map(|m| m.start)
+            .collect();
+        assert_eq!(found, [0, 7, 13, 20]);
+
+        let ac = AhoCorasick::new(&["Error"], MatchKind::Overlapping, false);
+        assert!(ac.find_all(b"ERROR error").is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Patterns can't be empty")]
+    fn empty_pattern() {
+        AhoCorasick::new(&["a", ""], MatchKind::Overlapping, false);
+    }
+
+    #[test]
+    fn against_brute_force() {
+        let mut rng = XorShift::new(47);
+        for _ in 0..300 {
+            let count = rng.range(1, 8);
+            let patterns: Vec<Vec<u8>> = (0..count)
+                .map(|_| {
+                    let len = rng.range(1, 5);
+                    random(&mut rng, len)
+                })
+                .collect();
+            let haystack = random(&mut rng, 80);
+            let ignore_case = rng.range(0, 2) == 1;
+            let haystack: Vec<u8> = if ignore_case {
+                haystack.iter().map(|b| b.to_ascii_uppercase()).collect()
+            } else {
+                haystack
+            };
+
+            let ac = AhoCorasick::new(&patterns, MatchKind::Overlapping, ignore_case);
+            assert_eq!(
+                ac.find_all(&haystack),
+                brute_overlapping(&haystack, &patterns, ignore_case)
+            );
+
+            let ac = AhoCorasick::new(&patterns, MatchKind::LeftmostLongest, false);
+            let haystack = haystack.to_ascii_lowercase();
+            assert_eq!(
+                ac.find_all(&haystack),
+                brute_leftmost_longest(&haystack, &patterns)
+            );
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let mut rng = XorShift::new(48);
+        for kind in [MatchKind::Overlapping, MatchKind::LeftmostLongest] {
+            for _ in 0..100 {
+                let patterns: Vec<Vec<u8>> = (0..6)
+                    .map(|_| {
+                        let len = rng.range(1, 7);
+                        random(&mut rng, len)
+                    })
+                    .collect();
+                let haystack = random(&mut rng, 200);
+                let ac = AhoCorasick::new(&patterns, kind, false);
+                let expected = ac.find_all(&haystack);
+
+                // Cut into random pieces, including empty ones
+                let mut stream = ac.stream();
+                let mut found = Vec::new();
+                let mut pos = 0;
+                while pos < haystack.len() {
+                    let end = (pos + rng.range(0, 10)).min(haystack.len());
+                    found.extend(stream.feed(&haystack[pos..end]));
+                    // Never holds on to more than the longest pattern
+                    assert!(stream.buffered() <= 6);
+                    pos = end;
+                }
+                found.extend(stream.finish());
+
+                assert_eq!(found, expected, "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn thousands_of_keywords() {
+        let mut rng = XorShift::new(49);
+        let keywords: Vec<String> = (0..5000)
+            .map(|i| format!("key{}x{}", i, rng.range(0, 100)))
+            .collect();
+        let ac = AhoCorasick::new(&keywords, MatchKind::LeftmostLongest, false);
+
+        let mut log = String::new();
+        for i in (0..5000).step_by(7) {
+            log.push_str("... ");
+            log.push_str(&keywords[i]);
+        }
+
+        let found = ac.find_all(log.as_bytes());
+        let patterns: Vec<usize> = found.iter().map(|m| m.pattern).collect();
+        assert_eq!(patterns, (0..5000).step_by(7).collect::<Vec<_>>());
+    }
+}
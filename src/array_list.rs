@@ -31,6 +31,10 @@ where
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn cap(&self) -> usize {
         self.cap
     }
@@ -39,9 +43,25 @@ where
         unsafe { slice::from_raw_parts(self.array, self.cap) }
     }
 
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        if idx >= self.len {
+            return None;
+        }
+
+        unsafe { Some(&*self.array.add(idx)) }
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.get(self.len - 1)
+    }
+
     pub fn push(&mut self, value: T) {
         if self.len == self.cap {
-            self.resize(self.cap * 2);
+            self.grow();
         }
 
         unsafe {
@@ -89,7 +109,7 @@ where
 
     pub fn push_front(&mut self, value: T) {
         if self.len == self.cap {
-            self.resize(self.cap * 2);
+            self.grow();
         }
 
         for i in (0..self.len).rev() {
@@ -121,6 +141,12 @@ where
         array
     }
 
+    fn grow(&mut self) {
+        // Doubling 0 would leave us with no room at all
+        let cap = if self.cap == 0 { 1 } else { self.cap * 2 };
+        self.resize(cap);
+    }
+
     fn resize(&mut self, cap: usize) {
         let new_array = Self::with_capacity(cap);
         unsafe {
@@ -192,6 +218,31 @@ mod tests {
         assert_eq!(list.cap(), prev_cap * 2);
     }
 
+    #[test]
+    fn growing_from_zero() {
+        let mut list = ArrayList::<u32>::new(0);
+        assert_eq!(list.cap(), 0);
+
+        list.push(1);
+        assert_eq!(list.cap(), 1);
+        list.push(2);
+        assert_eq!(list.print(), &[1, 2]);
+    }
+
+    #[test]
+    fn get_and_last() {
+        let mut list = ArrayList::<u32>::new(2);
+        assert_eq!(list.last(), None);
+
+        list.push(1);
+        list.push(2);
+        list.push(3);
+        assert_eq!(list.get(0), Some(&1));
+        assert_eq!(list.get(2), Some(&3));
+        assert_eq!(list.get(3), None);
+        assert_eq!(list.last(), Some(&3));
+    }
+
     #[test]
     fn pop() {
         let initial_cap = 4;
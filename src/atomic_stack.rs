@@ -0,0 +1,357 @@
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+// Treiber stack. Popping threads publish the node they are about to read in a hazard slot,
+// and popped nodes are only freed once nobody has them published. That also takes care of ABA:
+// a node can't be freed and reallocated at the same address while someone is still looking at it.
+
+// How many retired nodes we collect before trying to free them
+const RETIRE_THRESHOLD: usize = 64;
+
+struct Node<T> {
+    value: ManuallyDrop<T>,
+    // Written once before the node gets published, read-only afterwards
+    next: *mut Node<T>,
+    // Only touched by the thread retiring the node and by whoever frees it later
+    next_retired: *mut Node<T>,
+}
+
+struct Hazard {
+    ptr: AtomicPtr<()>,
+    active: AtomicBool,
+    // Hazards are never unlinked, so once set this never changes
+    next: *mut Hazard,
+}
+
+pub struct AtomicStack<T> {
+    head: AtomicPtr<Node<T>>,
+    length: AtomicUsize,
+    hazards: AtomicPtr<Hazard>,
+    retired: AtomicPtr<Node<T>>,
+    retired_count: AtomicUsize,
+}
+
+unsafe impl<T: Send> Send for AtomicStack<T> {}
+unsafe impl<T: Send> Sync for AtomicStack<T> {}
+
+impl<T> AtomicStack<T> {
+    pub fn new() -> Self {
+        AtomicStack {
+            head: AtomicPtr::new(ptr::null_mut()),
+            length: AtomicUsize::new(0),
+            hazards: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retired_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(Node {
+            value: ManuallyDrop::new(value),
+            next: ptr::null_mut(),
+            next_retired: ptr::null_mut(),
+        }));
+
+        // Counting before publishing, so a racing pop never takes the length below zero
+        self.length.fetch_add(1, Ordering::Relaxed);
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // Nobody else can see the node yet, so it's fine to write to it in the loop
+            unsafe { (*node).next = head };
+
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let hazard = self.acquire_hazard();
+
+        let node = loop {
+            let head = self.head.load(Ordering::Acquire);
+            if head.is_null() {
+                break head;
+            }
+
+            // Publish the node, then make sure it's still the head. If it is, nobody
+            // could have freed it in between, and nobody will until we clear the hazard.
+            hazard.ptr.store(head as *mut (), Ordering::SeqCst);
+            if self.head.load(Ordering::SeqCst) != head {
+                continue;
+            }
+
+            let next = unsafe { (*head).next };
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                break head;
+            }
+        };
+
+        hazard.ptr.store(ptr::null_mut(), Ordering::Release);
+        hazard.active.store(false, Ordering::Release);
+
+        if node.is_null() {
+            return None;
+        }
+
+        self.length.fetch_sub(1, Ordering::Relaxed);
+
+        // We won the CAS, so the value is ours. Other threads may still read `next`, but never the value.
+        let value = unsafe { ptr::read(&*(*node).value) };
+        self.retire(node);
+
+        Some(value)
+    }
+
+    // Takes everything at once, newest first. A single swap, so no ABA to worry about here.
+    pub fn pop_all(&self) -> Vec<T> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::AcqRel);
+        let mut values = Vec::new();
+
+        while !node.is_null() {
+            let next = unsafe { (*node).next };
+            values.push(unsafe { ptr::read(&*(*node).value) });
+            // Someone might have loaded one of these as head right before the swap
+            self.retire(node);
+            node = next;
+        }
+
+        self.length.fetch_sub(values.len(), Ordering::Relaxed);
+
+        values
+    }
+
+    // Only a snapshot, other threads can change it right after
+    pub fn len(&self) -> usize {
+        self.length.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    fn acquire_hazard(&self) -> &Hazard {
+        let mut current = self.hazards.load(Ordering::Acquire);
+        while !current.is_null() {
+            let hazard = unsafe { &*current };
+            if !hazard.active.load(Ordering::Relaxed)
+                && hazard
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return hazard;
+            }
+            current = hazard.next;
+        }
+
+        // All hazards are taken, add a new one to the front of the list
+        let hazard = Box::into_raw(Box::new(Hazard {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.hazards.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*hazard).next = head };
+            match self.hazards.compare_exchange_weak(
+                head,
+                hazard,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return unsafe { &*hazard },
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn retire(&self, node: *mut Node<T>) {
+        // Counting first: once the node is on the list another thread may free it and
+        // subtract it from the count right away
+        let retired = self.retired_count.fetch_add(1, Ordering::AcqRel) + 1;
+        self.push_retired(node);
+
+        if retired >= RETIRE_THRESHOLD {
+            self.reclaim();
+        }
+    }
+
+    fn push_retired(&self, node: *mut Node<T>) {
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*node).next_retired = head };
+            match self.retired.compare_exchange_weak(
+                head,
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn reclaim(&self) {
+        // Taking the whole retired list, whatever is still protected goes back on it
+        let mut node = self.retired.swap(ptr::null_mut(), Ordering::AcqRel);
+        if node.is_null() {
+            return;
+        }
+
+        let mut protected = Vec::new();
+        let mut hazard = self.hazards.load(Ordering::SeqCst);
+        while !hazard.is_null() {
+            let ptr = unsafe { (*hazard).ptr.load(Ordering::SeqCst) };
+            if !ptr.is_null() {
+                protected.push(ptr);
+            }
+            hazard = unsafe { (*hazard).next };
+        }
+
+        let mut freed = 0;
+        while !node.is_null() {
+            let next = unsafe { (*node).next_retired };
+            if protected.contains(&(node as *mut ())) {
+                self.push_retired(node);
+            } else {
+                // The value has been moved out already and ManuallyDrop keeps it from being dropped again
+                drop(unsafe { Box::from_raw(node) });
+                freed += 1;
+            }
+            node = next;
+        }
+
+        self.retired_count.fetch_sub(freed, Ordering::AcqRel);
+    }
+}
+
+impl<T> Default for AtomicStack<T> {
+    fn default() -> Self {
+        AtomicStack::new()
+    }
+}
+
+impl<T> Drop for AtomicStack<T> {
+    fn drop(&mut self) {
+        // We have &mut self, so there are no other threads around anymore
+        let mut node = *self.head.get_mut();
+        while !node.is_null() {
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { ManuallyDrop::drop(&mut boxed.value) };
+            node = boxed.next;
+        }
+
+        let mut node = *self.retired.get_mut();
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next_retired;
+        }
+
+        let mut hazard = *self.hazards.get_mut();
+        while !hazard.is_null() {
+            let boxed = unsafe { Box::from_raw(hazard) };
+            hazard = boxed.next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atomic_stack::AtomicStack;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_1() {
+        let stack: AtomicStack<u32> = AtomicStack::new();
+        assert_eq!(stack.pop(), None);
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+        stack.push(4);
+        assert_eq!(stack.pop_all(), vec![4, 1]);
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn drops_remaining_values() {
+        let counter = Rc::new(());
+        {
+            let stack = AtomicStack::new();
+            for _ in 0..200 {
+                stack.push(Rc::clone(&counter));
+            }
+            for _ in 0..150 {
+                stack.pop();
+            }
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    struct Tracked(Arc<AtomicUsize>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn stress() {
+        let threads = 8;
+        let per_thread = 20_000;
+        let stack = AtomicStack::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+
+        let popped: Vec<Vec<usize>> = thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let stack = &stack;
+                    let drops = &drops;
+                    s.spawn(move || {
+                        let mut popped = Vec::new();
+                        for i in 0..per_thread {
+                            stack.push((t * per_thread + i, Tracked(Arc::clone(drops))));
+                            if i % 3 == 0 {
+                                popped.extend(stack.pop_all().into_iter().map(|(v, _)| v));
+                            } else if let Some((value, _)) = stack.pop() {
+                                popped.push(value);
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let mut all: Vec<usize> = popped.into_iter().flatten().collect();
+        all.extend(stack.pop_all().into_iter().map(|(v, _)| v));
+        all.sort();
+
+        // Every value shows up exactly once
+        assert_eq!(all, (0..threads * per_thread).collect::<Vec<usize>>());
+        assert_eq!(drops.load(Ordering::Relaxed), threads * per_thread);
+    }
+}
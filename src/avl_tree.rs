@@ -0,0 +1,432 @@
+use crate::ordered_map::{self, BinaryNode, OrderedMap};
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::RangeBounds;
+
+type Link<K, V> = Option<Box<AvlNode<K, V>>>;
+
+struct AvlNode<K, V> {
+    key: K,
+    value: V,
+    height: usize, // of the subtree, a leaf is 1
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K, V> AvlNode<K, V> {
+    fn new(key: K, value: V) -> Box<AvlNode<K, V>> {
+        Box::new(AvlNode {
+            key,
+            value,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update(&mut self) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+    }
+
+    // Positive when the left side is taller
+    fn balance(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+impl<K, V> BinaryNode<K, V> for AvlNode<K, V> {
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+// Binary search tree where the two sides of every node differ in height by one at most,
+// so it's never more than about 1.44 * log2(n) deep. Every insert or remove fixes that up
+// on the way back to the root with one or two rotations per node that got out of balance.
+pub struct AvlMap<K: Ord, V> {
+    root: Link<K, V>,
+    length: usize,
+}
+
+impl<K: Ord, V> AvlMap<K, V> {
+    pub fn new() -> AvlMap<K, V> {
+        AvlMap {
+            root: None,
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut old = None;
+        self.root = Some(insert(self.root.take(), key, value, &mut old));
+        if old.is_none() {
+            self.length += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let mut removed = None;
+        self.root = remove(self.root.take(), key, &mut removed);
+        if removed.is_some() {
+            self.length -= 1;
+        }
+        removed
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        ordered_map::get(self.root.as_deref(), key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        ordered_map::min(self.root.as_deref())
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        ordered_map::max(self.root.as_deref())
+    }
+
+    // Biggest key that's <= key
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::floor(self.root.as_deref(), key)
+    }
+
+    // Smallest key that's >= key
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::ceiling(self.root.as_deref(), key)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        ordered_map::range(self.root.as_deref(), range)
+    }
+
+    // Left, node, right: sorted by key
+    pub fn in_order(&self) -> Vec<(&K, &V)> {
+        self.range(..)
+    }
+
+    pub fn pre_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::pre_order(self.root.as_deref())
+    }
+
+    pub fn post_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::post_order(self.root.as_deref())
+    }
+
+    pub fn level_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::level_order(self.root.as_deref())
+    }
+
+    // Nodes on the longest path from the root down, 0 when empty
+    pub fn height(&self) -> usize {
+        ordered_map::height(self.root.as_deref())
+    }
+
+    // Goes through the whole tree, for tests
+    pub fn check(&self) -> Result<(), String> {
+        let mut count = 0;
+        check(&self.root, None, None, &mut count)?;
+        if count != self.length {
+            return Err(format!("Has {} nodes but length is {}", count, self.length));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Ord, V> Default for AvlMap<K, V> {
+    fn default() -> Self {
+        AvlMap::new()
+    }
+}
+
+impl<K: Ord, V> OrderedMap<K, V> for AvlMap<K, V> {
+    fn len(&self) -> usize {
+        AvlMap::len(self)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        AvlMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        AvlMap::remove(self, key)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        AvlMap::get(self, key)
+    }
+
+    fn min(&self) -> Option<(&K, &V)> {
+        AvlMap::min(self)
+    }
+
+    fn max(&self) -> Option<(&K, &V)> {
+        AvlMap::max(self)
+    }
+
+    fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        AvlMap::floor(self, key)
+    }
+
+    fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        AvlMap::ceiling(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        AvlMap::range(self, range)
+    }
+
+    fn in_order(&self) -> Vec<(&K, &V)> {
+        AvlMap::in_order(self)
+    }
+
+    fn pre_order(&self) -> Vec<(&K, &V)> {
+        AvlMap::pre_order(self)
+    }
+
+    fn post_order(&self) -> Vec<(&K, &V)> {
+        AvlMap::post_order(self)
+    }
+
+    fn level_order(&self) -> Vec<(&K, &V)> {
+        AvlMap::level_order(self)
+    }
+
+    fn height(&self) -> usize {
+        AvlMap::height(self)
+    }
+}
+
+fn height<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+//     node          left
+//     /  \          /  \
+//   left  c  ->    a   node
+//   /  \               /  \
+//  a    b             b    c
+fn rotate_right<K, V>(mut node: Box<AvlNode<K, V>>) -> Box<AvlNode<K, V>> {
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    node.update();
+    left.right = Some(node);
+    left.update();
+    left
+}
+
+fn rotate_left<K, V>(mut node: Box<AvlNode<K, V>>) -> Box<AvlNode<K, V>> {
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    node.update();
+    right.left = Some(node);
+    right.update();
+    right
+}
+
+// Children are balanced and at most 2 apart in height, makes the node balanced too
+fn rebalance<K, V>(mut node: Box<AvlNode<K, V>>) -> Box<AvlNode<K, V>> {
+    node.update();
+
+    if node.balance() > 1 {
+        // Left-right: the middle subtree is the tall one, turn it into left-left first
+        if node.left.as_ref().unwrap().balance() < 0 {
+            node.left = Some(rotate_left(node.left.take().unwrap()));
+        }
+        return rotate_right(node);
+    }
+
+    if node.balance() < -1 {
+        if node.right.as_ref().unwrap().balance() > 0 {
+            node.right = Some(rotate_right(node.right.take().unwrap()));
+        }
+        return rotate_left(node);
+    }
+
+    node
+}
+
+fn insert<K: Ord, V>(
+    link: Link<K, V>,
+    key: K,
+    value: V,
+    old: &mut Option<V>,
+) -> Box<AvlNode<K, V>> {
+    let Some(mut node) = link else {
+        return AvlNode::new(key, value);
+    };
+
+    match key.cmp(&node.key) {
+        Ordering::Less => node.left = Some(insert(node.left.take(), key, value, old)),
+        Ordering::Greater => node.right = Some(insert(node.right.take(), key, value, old)),
+        Ordering::Equal => {
+            *old = Some(mem::replace(&mut node.value, value));
+            return node;
+        }
+    }
+
+    rebalance(node)
+}
+
+fn remove<K: Ord, V>(link: Link<K, V>, key: &K, removed: &mut Option<V>) -> Link<K, V> {
+    let mut node = link?;
+
+    match key.cmp(&node.key) {
+        Ordering::Less => node.left = remove(node.left.take(), key, removed),
+        Ordering::Greater => node.right = remove(node.right.take(), key, removed),
+        Ordering::Equal => {
+            let replacement = match (node.left.take(), node.right.take()) {
+                (None, None) => None,
+                (Some(child), None) | (None, Some(child)) => Some(child),
+                // The smallest on the right takes its place, like in BstMap
+                (Some(left), Some(right)) => {
+                    let (rest, mut successor) = remove_min(right);
+                    successor.left = Some(left);
+                    successor.right = rest;
+                    Some(rebalance(successor))
+                }
+            };
+            *removed = Some(node.value);
+            return replacement;
+        }
+    }
+
+    Some(rebalance(node))
+}
+
+// (what's left of the subtree, its smallest node)
+fn remove_min<K, V>(mut node: Box<AvlNode<K, V>>) -> (Link<K, V>, Box<AvlNode<K, V>>) {
+    match node.left.take() {
+        None => (node.right.take(), node),
+        Some(left) => {
+            let (rest, min) = remove_min(left);
+            node.left = rest;
+            (Some(rebalance(node)), min)
+        }
+    }
+}
+
+// Every key between lo and hi, heights right and balanced. Gives back the height.
+fn check<K: Ord, V>(
+    link: &Link<K, V>,
+    lo: Option<&K>,
+    hi: Option<&K>,
+    count: &mut usize,
+) -> Result<usize, String> {
+    let Some(node) = link else {
+        return Ok(0);
+    };
+    *count += 1;
+
+    if lo.is_some_and(|lo| node.key <= *lo) || hi.is_some_and(|hi| node.key >= *hi) {
+        return Err("Keys out of order".to_string());
+    }
+
+    let left = check(&node.left, lo, Some(&node.key), count)?;
+    let right = check(&node.right, Some(&node.key), hi, count)?;
+
+    if node.height != 1 + left.max(right) {
+        return Err(format!(
+            "Stored height {} should be {}",
+            node.height,
+            1 + left.max(right)
+        ));
+    }
+    if left.abs_diff(right) > 1 {
+        return Err(format!("Out of balance, heights {} and {}", left, right));
+    }
+
+    Ok(node.height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test() {
+        let mut map = AvlMap::new();
+        for key in 0..1000 {
+            assert_eq!(map.insert(key, key * 10), None);
+        }
+        map.check().unwrap();
+        // Sorted keys, but still log deep: a BstMap would be 1000
+        assert!(map.height() <= 11);
+
+        assert_eq!(map.insert(5, 0), Some(50));
+        assert_eq!(map.get(&5), Some(&0));
+        assert_eq!(map.floor(&-1), None);
+        assert_eq!(map.ceiling(&999), Some((&999, &9990)));
+
+        for key in (0..1000).step_by(2) {
+            assert_eq!(map.remove(&key), Some(key * 10));
+        }
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.remove(&0), None);
+        map.check().unwrap();
+        assert_eq!(map.min(), Some((&1, &10)));
+    }
+
+    #[test]
+    fn rotations() {
+        // Right-right, left-left, right-left and left-right each end up with 2 on top
+        for keys in [[1, 2, 3], [3, 2, 1], [1, 3, 2], [3, 1, 2]] {
+            let mut map = AvlMap::new();
+            for key in keys {
+                map.insert(key, ());
+                map.check().unwrap();
+            }
+            let pre: Vec<i32> = map.pre_order().into_iter().map(|(k, _)| *k).collect();
+            assert_eq!(pre, [2, 1, 3], "{:?}", keys);
+        }
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut rng = XorShift::new(50);
+        let mut map = AvlMap::new();
+        let mut expected = BTreeMap::new();
+
+        for _ in 0..20000 {
+            let key = rng.range(0, 300);
+            match rng.range(0, 3) {
+                0 | 1 => assert_eq!(map.insert(key, key * 2), expected.insert(key, key * 2)),
+                _ => assert_eq!(map.remove(&key), expected.remove(&key)),
+            }
+            map.check().unwrap();
+            assert_eq!(map.len(), expected.len());
+            assert_eq!(map.get(&key), expected.get(&key));
+            assert_eq!(map.floor(&key), expected.range(..=key).next_back());
+            assert_eq!(map.ceiling(&key), expected.range(key..).next());
+        }
+
+        let all: Vec<_> = expected.iter().collect();
+        assert_eq!(map.in_order(), all);
+    }
+}
@@ -1,21 +1,221 @@
+use std::cmp::Ordering;
+use std::ops::{Range, RangeInclusive};
+
 pub fn binary_search_list(haystack: [u32; 10], needle: u32) -> bool {
+    binary_search(&haystack, &needle).is_ok()
+}
+
+// Ok with the index of the needle (the first one if there are duplicates), otherwise
+// Err with the index it would have to be inserted at to keep arr sorted
+pub fn binary_search<T: Ord>(arr: &[T], needle: &T) -> Result<usize, usize> {
+    binary_search_by(arr, |x| x.cmp(needle))
+}
+
+pub fn binary_search_by_key<T, K: Ord, F>(arr: &[T], needle: &K, mut key: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> K,
+{
+    binary_search_by(arr, |x| key(x).cmp(needle))
+}
+
+// f says how an element compares to what we're looking for, like std's binary_search_by
+pub fn binary_search_by<T, F>(arr: &[T], mut f: F) -> Result<usize, usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let i = lower_bound_by(arr, &mut f);
+    if i < arr.len() && f(&arr[i]) == Ordering::Equal {
+        Ok(i)
+    } else {
+        Err(i)
+    }
+}
+
+pub fn lower_bound<T: Ord>(arr: &[T], needle: &T) -> usize {
+    lower_bound_by(arr, |x| x.cmp(needle))
+}
+
+pub fn lower_bound_by_key<T, K: Ord, F: FnMut(&T) -> K>(
+    arr: &[T],
+    needle: &K,
+    mut key: F,
+) -> usize {
+    lower_bound_by(arr, |x| key(x).cmp(needle))
+}
+
+// First index whose element is not less than the needle
+pub fn lower_bound_by<T, F>(arr: &[T], mut f: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(arr, |x| f(x) == Ordering::Less)
+}
+
+pub fn upper_bound<T: Ord>(arr: &[T], needle: &T) -> usize {
+    upper_bound_by(arr, |x| x.cmp(needle))
+}
+
+pub fn upper_bound_by_key<T, K: Ord, F: FnMut(&T) -> K>(
+    arr: &[T],
+    needle: &K,
+    mut key: F,
+) -> usize {
+    upper_bound_by(arr, |x| key(x).cmp(needle))
+}
+
+// First index whose element is greater than the needle
+pub fn upper_bound_by<T, F>(arr: &[T], mut f: F) -> usize
+where
+    F: FnMut(&T) -> Ordering,
+{
+    partition_point(arr, |x| f(x) != Ordering::Greater)
+}
+
+pub fn equal_range<T: Ord>(arr: &[T], needle: &T) -> Range<usize> {
+    equal_range_by(arr, |x| x.cmp(needle))
+}
+
+pub fn equal_range_by_key<T, K: Ord, F: FnMut(&T) -> K>(
+    arr: &[T],
+    needle: &K,
+    mut key: F,
+) -> Range<usize> {
+    equal_range_by(arr, |x| key(x).cmp(needle))
+}
+
+// All the elements equal to the needle, empty (at the insertion point) if there are none
+pub fn equal_range_by<T, F>(arr: &[T], mut f: F) -> Range<usize>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    let lo = lower_bound_by(arr, &mut f);
+    // Everything before lo is less, so only the rest needs looking at
+    let hi = lo + upper_bound_by(&arr[lo..], &mut f);
+    lo..hi
+}
+
+// arr has to be split into a part where pred holds followed by a part where it doesn't,
+// returns where the second part starts
+pub fn partition_point<T, P>(arr: &[T], mut pred: P) -> usize
+where
+    P: FnMut(&T) -> bool,
+{
     let mut lo = 0;
-    let mut hi = haystack.len();
+    let mut hi = arr.len();
 
     while lo < hi {
         let m = lo + (hi - lo) / 2;
-        let v = haystack[m];
-
-        if v == needle {
-            return true;
-        } else if v > needle {
+        if pred(&arr[m]) {
+            lo = m + 1;
+        } else {
             hi = m;
+        }
+    }
+
+    lo
+}
+
+// partition_point over every integer in range instead of a slice: the first x where pred
+// is false, None if it holds for the whole range. Inclusive, so the type's extremes can be
+// searched too, and the midpoint is taken so that nothing overflows on the way.
+pub fn partition_point_u64<P>(range: RangeInclusive<u64>, mut pred: P) -> Option<u64>
+where
+    P: FnMut(u64) -> bool,
+{
+    if range.is_empty() {
+        return None;
+    }
+
+    let (mut lo, mut hi) = range.into_inner();
+    // From here on the answer is always somewhere in lo..=hi
+    if pred(hi) {
+        return None;
+    }
+
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if pred(m) {
+            lo = m + 1; // m < hi, so this can't overflow
         } else {
+            hi = m;
+        }
+    }
+
+    Some(lo)
+}
+
+pub fn partition_point_i64<P>(range: RangeInclusive<i64>, mut pred: P) -> Option<i64>
+where
+    P: FnMut(i64) -> bool,
+{
+    if range.is_empty() {
+        return None;
+    }
+
+    let (mut lo, mut hi) = range.into_inner();
+    if pred(hi) {
+        return None;
+    }
+
+    while lo < hi {
+        // hi - lo doesn't fit in an i64 for the full range, but half of it always does
+        let m = lo + (hi.abs_diff(lo) / 2) as i64;
+        if pred(m) {
             lo = m + 1;
+        } else {
+            hi = m;
         }
     }
 
-    false
+    Some(lo)
+}
+
+// Same for a real interval, where the answer can only be narrowed down. Returns a point
+// where pred is false that's within eps of where pred starts being false, or the best one
+// found after max_iterations. Stops early once there's no float left between the bounds,
+// so an eps of 0 is fine too.
+pub fn partition_point_f64<P>(
+    lo: f64,
+    hi: f64,
+    eps: f64,
+    max_iterations: usize,
+    mut pred: P,
+) -> Option<f64>
+where
+    P: FnMut(f64) -> bool,
+{
+    if !lo.is_finite() || !hi.is_finite() {
+        panic!("Interval has to be finite");
+    }
+
+    if lo > hi || pred(hi) {
+        return None;
+    }
+    if !pred(lo) {
+        return Some(lo);
+    }
+
+    // pred(lo) holds and pred(hi) doesn't the whole time
+    let (mut lo, mut hi) = (lo, hi);
+    for _ in 0..max_iterations {
+        if hi - lo <= eps {
+            break;
+        }
+
+        // (lo + hi) / 2 overflows for bounds near f64::MAX
+        let m = lo / 2.0 + hi / 2.0;
+        if m <= lo || m >= hi {
+            break;
+        }
+
+        if pred(m) {
+            lo = m;
+        } else {
+            hi = m;
+        }
+    }
+
+    Some(hi)
 }
 
 #[cfg(test)]
@@ -23,6 +223,7 @@ mod tests {
     use super::*; // Import the parent module
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test() {
         assert_eq!(
             binary_search_list([1, 2, 3, 4, 5, 6, 7, 12, 13, 33], 5),
@@ -37,4 +238,173 @@ mod tests {
             false
         );
     }
+
+    #[test]
+    fn bounds() {
+        let arr = [1, 3, 3, 3, 5, 8];
+        assert_eq!(binary_search(&arr, &3), Ok(1));
+        assert_eq!(binary_search(&arr, &4), Err(4));
+        assert_eq!(binary_search(&arr, &9), Err(6));
+        assert_eq!(lower_bound(&arr, &3), 1);
+        assert_eq!(upper_bound(&arr, &3), 4);
+        assert_eq!(equal_range(&arr, &3), 1..4);
+        assert_eq!(equal_range(&arr, &0), 0..0);
+        assert_eq!(equal_range(&arr, &6), 5..5);
+    }
+
+    #[test]
+    fn by_key() {
+        let arr = [("a", 1), ("b", 2), ("c", 2), ("d", 7)];
+        assert_eq!(binary_search_by_key(&arr, &2, |p| p.1), Ok(1));
+        assert_eq!(binary_search_by_key(&arr, &3, |p| p.1), Err(3));
+        assert_eq!(lower_bound_by_key(&arr, &2, |p| p.1), 1);
+        assert_eq!(upper_bound_by_key(&arr, &2, |p| p.1), 3);
+        assert_eq!(equal_range_by_key(&arr, &2, |p| p.1), 1..3);
+
+        // Sorted descending, so the comparison is flipped
+        let arr = [9, 7, 7, 2];
+        assert_eq!(binary_search_by(&arr, |x| 7.cmp(x)), Ok(1));
+        assert_eq!(equal_range_by(&arr, |x| 7.cmp(x)), 1..3);
+    }
+
+    // Every non-decreasing array of length up to `len` with values in 0..values
+    fn sorted_arrays(len: usize, values: i32) -> Vec<Vec<i32>> {
+        let mut all = vec![vec![]];
+        let mut last = vec![vec![]];
+        for _ in 0..len {
+            let mut next = Vec::new();
+            for arr in last.iter() {
+                let from = arr.last().copied().unwrap_or(0);
+                for v in from..values {
+                    let mut longer = arr.clone();
+                    longer.push(v);
+                    next.push(longer);
+                }
+            }
+            all.extend(next.iter().cloned());
+            last = next;
+        }
+        all
+    }
+
+    #[test]
+    fn exhaustive() {
+        for arr in sorted_arrays(7, 4) {
+            for needle in -1..=4 {
+                let lower = arr.iter().filter(|&&x| x < needle).count();
+                let upper = arr.iter().filter(|&&x| x <= needle).count();
+
+                assert_eq!(lower_bound(&arr, &needle), lower, "{:?} {}", arr, needle);
+                assert_eq!(upper_bound(&arr, &needle), upper, "{:?} {}", arr, needle);
+                assert_eq!(equal_range(&arr, &needle), lower..upper);
+
+                let expected = if lower < upper { Ok(lower) } else { Err(lower) };
+                assert_eq!(
+                    binary_search(&arr, &needle),
+                    expected,
+                    "{:?} {}",
+                    arr,
+                    needle
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn integer_ranges() {
+        assert_eq!(partition_point_u64(0..=100, |x| x * x < 50), Some(8));
+        assert_eq!(partition_point_u64(0..=100, |_| true), None);
+        assert_eq!(partition_point_u64(0..=100, |_| false), Some(0));
+        let (lo, hi) = (5, 4);
+        assert_eq!(partition_point_u64(lo..=hi, |_| false), None);
+
+        assert_eq!(partition_point_i64(-100..=100, |x| x < -5), Some(-5));
+        assert_eq!(partition_point_i64(-100..=100, |x| x <= 100), None);
+    }
+
+    #[test]
+    fn type_extremes() {
+        let mut calls = 0;
+        let found = partition_point_u64(0..=u64::MAX, |x| {
+            calls += 1;
+            x < u64::MAX
+        });
+        assert_eq!(found, Some(u64::MAX));
+        assert!(calls <= 65);
+
+        assert_eq!(
+            partition_point_u64(0..=u64::MAX, |x| x < 1 << 63),
+            Some(1 << 63)
+        );
+        assert_eq!(
+            partition_point_u64(u64::MAX..=u64::MAX, |_| false),
+            Some(u64::MAX)
+        );
+
+        for boundary in [i64::MIN, i64::MIN + 1, -1, 0, 1, i64::MAX - 1, i64::MAX] {
+            let mut calls = 0;
+            let found = partition_point_i64(i64::MIN..=i64::MAX, |x| {
+                calls += 1;
+                x < boundary
+            });
+            assert_eq!(found, Some(boundary));
+            assert!(calls <= 65);
+        }
+        assert_eq!(partition_point_i64(i64::MIN..=i64::MAX, |_| true), None);
+    }
+
+    #[test]
+    fn smallest_capacity() {
+        // Smallest ship that carries all the packages, in order, within 5 days
+        let weights = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let too_small = |capacity: u64| {
+            let mut days = 1;
+            let mut load = 0;
+            for &w in weights.iter() {
+                if w > capacity {
+                    return true;
+                }
+                if load + w > capacity {
+                    days += 1;
+                    load = 0;
+                }
+                load += w;
+            }
+            days > 5
+        };
+        assert_eq!(partition_point_u64(1..=55, too_small), Some(15));
+    }
+
+    #[test]
+    fn real_interval() {
+        let root = partition_point_f64(0.0, 2.0, 1e-12, 100, |x| x * x < 2.0).unwrap();
+        assert!((root - 2f64.sqrt()).abs() <= 1e-12);
+        assert!(root * root >= 2.0);
+
+        assert_eq!(partition_point_f64(0.0, 2.0, 1e-9, 100, |_| true), None);
+        assert_eq!(
+            partition_point_f64(0.0, 2.0, 1e-9, 100, |_| false),
+            Some(0.0)
+        );
+
+        // Goes all the way down to neighbouring floats with eps 0, and still stops
+        let exact = partition_point_f64(0.0, 1.0, 0.0, 10_000, |x| x < 0.3).unwrap();
+        assert_eq!(exact, 0.3);
+
+        // Bounds near f64::MAX would overflow a naive midpoint
+        let big = partition_point_f64(-f64::MAX, f64::MAX, 0.0, 10_000, |x| x < 1e300);
+        assert_eq!(big, Some(1e300));
+    }
+
+    #[test]
+    fn iteration_limit() {
+        let mut calls = 0;
+        let found = partition_point_f64(0.0, 1.0, 0.0, 10, |x| {
+            calls += 1;
+            x < 0.5f64.sqrt()
+        });
+        // One call each for the bounds, then one per iteration
+        assert_eq!(calls, 12);
+        assert!((found.unwrap() - 0.5f64.sqrt()).abs() < 1.0 / 1024.0);
+    }
 }
@@ -0,0 +1,393 @@
+use crate::ordered_map::{self, BinaryNode, OrderedMap};
+use std::cmp::Ordering;
+use std::ops::RangeBounds;
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+pub struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V) -> Box<Node<K, V>> {
+        Box::new(Node {
+            key,
+            value,
+            left: None,
+            right: None,
+        })
+    }
+}
+
+impl<K, V> BinaryNode<K, V> for Node<K, V> {
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+// Plain binary search tree, smaller keys to the left. Nothing keeps it balanced, so keys
+// coming in sorted turn it into a linked list. Everything walks the tree in a loop rather
+// than recursing, that way a tree like that is slow but doesn't blow the stack.
+pub struct BstMap<K: Ord, V> {
+    root: Link<K, V>,
+    length: usize,
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    pub fn new() -> BstMap<K, V> {
+        BstMap {
+            root: None,
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // Gives back the old value if the key was there
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let link = find_link(&mut self.root, &key);
+        match link {
+            Some(node) => Some(std::mem::replace(&mut node.value, value)),
+            None => {
+                *link = Some(Node::new(key, value));
+                self.length += 1;
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        ordered_map::get(self.root.as_deref(), key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        find_link(&mut self.root, key)
+            .as_mut()
+            .map(|node| &mut node.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let link = find_link(&mut self.root, key);
+        let mut node = link.take()?;
+
+        *link = match (node.left.take(), node.right.take()) {
+            // A leaf just goes
+            (None, None) => None,
+            // One child moves up into its place
+            (Some(child), None) | (None, Some(child)) => Some(child),
+            // Two children: the smallest key on the right is bigger than everything on the
+            // left and smaller than the rest on the right, so it can take the node's place
+            (Some(left), Some(right)) => {
+                let mut right = Some(right);
+                let mut successor = take_min(&mut right);
+                successor.left = Some(left);
+                successor.right = right;
+                Some(successor)
+            }
+        };
+
+        self.length -= 1;
+        Some(node.value)
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        ordered_map::min(self.root.as_deref())
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        ordered_map::max(self.root.as_deref())
+    }
+
+    // Biggest key that's <= key
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::floor(self.root.as_deref(), key)
+    }
+
+    // Smallest key that's >= key
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::ceiling(self.root.as_deref(), key)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        ordered_map::range(self.root.as_deref(), range)
+    }
+
+    // Left, node, right: sorted by key
+    pub fn in_order(&self) -> Vec<(&K, &V)> {
+        self.range(..)
+    }
+
+    pub fn pre_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::pre_order(self.root.as_deref())
+    }
+
+    pub fn post_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::post_order(self.root.as_deref())
+    }
+
+    pub fn level_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::level_order(self.root.as_deref())
+    }
+
+    // Nodes on the longest path from the root down, 0 when empty
+    pub fn height(&self) -> usize {
+        ordered_map::height(self.root.as_deref())
+    }
+}
+
+impl<K: Ord, V> OrderedMap<K, V> for BstMap<K, V> {
+    fn len(&self) -> usize {
+        BstMap::len(self)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        BstMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BstMap::remove(self, key)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        BstMap::get(self, key)
+    }
+
+    fn min(&self) -> Option<(&K, &V)> {
+        BstMap::min(self)
+    }
+
+    fn max(&self) -> Option<(&K, &V)> {
+        BstMap::max(self)
+    }
+
+    fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        BstMap::floor(self, key)
+    }
+
+    fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        BstMap::ceiling(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        BstMap::range(self, range)
+    }
+
+    fn in_order(&self) -> Vec<(&K, &V)> {
+        BstMap::in_order(self)
+    }
+
+    fn pre_order(&self) -> Vec<(&K, &V)> {
+        BstMap::pre_order(self)
+    }
+
+    fn post_order(&self) -> Vec<(&K, &V)> {
+        BstMap::post_order(self)
+    }
+
+    fn level_order(&self) -> Vec<(&K, &V)> {
+        BstMap::level_order(self)
+    }
+
+    fn height(&self) -> usize {
+        BstMap::height(self)
+    }
+}
+
+impl<K: Ord, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        BstMap::new()
+    }
+}
+
+impl<K: Ord, V> Drop for BstMap<K, V> {
+    fn drop(&mut self) {
+        // Taking nodes apart one by one, dropping a long chain of boxes recurses as deep as it is
+        let mut stack: Vec<Box<Node<K, V>>> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+// The link that holds the key, or the empty one where it would go
+fn find_link<'a, K: Ord, V>(mut link: &'a mut Link<K, V>, key: &K) -> &'a mut Link<K, V> {
+    loop {
+        match link.as_ref().map(|node| key.cmp(&node.key)) {
+            None | Some(Ordering::Equal) => return link,
+            Some(Ordering::Less) => link = &mut link.as_mut().unwrap().left,
+            Some(Ordering::Greater) => link = &mut link.as_mut().unwrap().right,
+        }
+    }
+}
+
+// Unlinks the leftmost node under link, its right child takes its place
+fn take_min<K, V>(mut link: &mut Link<K, V>) -> Box<Node<K, V>> {
+    while link.as_ref().unwrap().left.is_some() {
+        link = &mut link.as_mut().unwrap().left;
+    }
+
+    let mut min = link.take().unwrap();
+    *link = min.right.take();
+    min
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+    use std::collections::BTreeMap;
+
+    fn keys<'a>(pairs: Vec<(&'a i32, &'a char)>) -> Vec<i32> {
+        pairs.into_iter().map(|(k, _)| *k).collect()
+    }
+
+    //         8
+    //      /     \
+    //     3       10
+    //    / \        \
+    //   1   6        14
+    //      / \      /
+    //     4   7   13
+    fn example() -> BstMap<i32, char> {
+        let mut map = BstMap::new();
+        for key in [8, 3, 10, 1, 6, 14, 4, 7, 13] {
+            map.insert(key, char::from(b'a' + key as u8));
+        }
+        map
+    }
+
+    #[test]
+    fn test() {
+        let mut map = example();
+        assert_eq!(map.len(), 9);
+        assert_eq!(map.get(&6), Some(&'g'));
+        assert_eq!(map.get(&5), None);
+        assert_eq!(map.insert(6, 'x'), Some('g'));
+        assert_eq!(map.len(), 9);
+        *map.get_mut(&6).unwrap() = 'y';
+        assert_eq!(map.get(&6), Some(&'y'));
+
+        assert_eq!(map.min(), Some((&1, &'b')));
+        assert_eq!(map.max(), Some((&14, &'o')));
+        assert_eq!(map.floor(&5).map(|(k, _)| *k), Some(4));
+        assert_eq!(map.floor(&0), None);
+        assert_eq!(map.ceiling(&11).map(|(k, _)| *k), Some(13));
+        assert_eq!(map.ceiling(&15), None);
+        assert_eq!(map.floor(&13).map(|(k, _)| *k), Some(13));
+
+        assert_eq!(keys(map.range(4..10)), [4, 6, 7, 8]);
+        assert_eq!(keys(map.range(..=4)), [1, 3, 4]);
+        assert_eq!(keys(map.range(11..)), [13, 14]);
+        assert_eq!(map.height(), 4);
+
+        let empty: BstMap<i32, i32> = BstMap::new();
+        assert_eq!(empty.min(), None);
+        assert!(empty.in_order().is_empty());
+        assert_eq!(empty.height(), 0);
+    }
+
+    #[test]
+    fn traversals() {
+        let map = example();
+        assert_eq!(keys(map.in_order()), [1, 3, 4, 6, 7, 8, 10, 13, 14]);
+        assert_eq!(keys(map.pre_order()), [8, 3, 1, 6, 4, 7, 10, 14, 13]);
+        assert_eq!(keys(map.post_order()), [1, 4, 7, 6, 3, 13, 14, 10, 8]);
+        assert_eq!(keys(map.level_order()), [8, 3, 10, 1, 6, 14, 4, 7, 13]);
+    }
+
+    #[test]
+    fn remove() {
+        // Leaf
+        let mut map = example();
+        assert_eq!(map.remove(&4), Some('e'));
+        assert_eq!(keys(map.pre_order()), [8, 3, 1, 6, 7, 10, 14, 13]);
+
+        // One child, 14 moves up under 8
+        let mut map = example();
+        assert_eq!(map.remove(&10), Some('k'));
+        assert_eq!(keys(map.pre_order()), [8, 3, 1, 6, 4, 7, 14, 13]);
+
+        // Two children, 4 is the smallest on the right of 3
+        let mut map = example();
+        assert_eq!(map.remove(&3), Some('d'));
+        assert_eq!(keys(map.pre_order()), [8, 4, 1, 6, 7, 10, 14, 13]);
+
+        // The root, its successor has a right child of its own
+        let mut map = example();
+        map.insert(11, 'l');
+        map.insert(12, 'm');
+        assert_eq!(map.remove(&8), Some('i'));
+        assert_eq!(keys(map.pre_order()), [10, 3, 1, 6, 4, 7, 14, 13, 11, 12]);
+
+        assert_eq!(map.remove(&8), None);
+        assert_eq!(map.len(), 10);
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut rng = XorShift::new(49);
+        let mut map = BstMap::new();
+        let mut expected = BTreeMap::new();
+
+        for _ in 0..20000 {
+            let key = rng.range(0, 500) as i32;
+            match rng.range(0, 4) {
+                0 | 1 => assert_eq!(map.insert(key, key * 2), expected.insert(key, key * 2)),
+                2 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                _ => {
+                    assert_eq!(map.get(&key), expected.get(&key));
+                    assert_eq!(map.floor(&key), expected.range(..=key).next_back());
+                    assert_eq!(map.ceiling(&key), expected.range(key..).next());
+
+                    let end = key + rng.range(0, 50) as i32;
+                    let found: Vec<_> = expected.range(key..end).collect();
+                    assert_eq!(map.range(key..end), found);
+                }
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+
+        let all: Vec<_> = expected.iter().collect();
+        assert_eq!(map.in_order(), all);
+        assert_eq!(map.min(), expected.first_key_value());
+        assert_eq!(map.max(), expected.last_key_value());
+    }
+
+    #[test]
+    fn sorted_inserts() {
+        // Turns into a list, but nothing recurses
+        let mut map = BstMap::new();
+        for key in 0..5000 {
+            map.insert(key, ());
+        }
+        assert_eq!(map.height(), 5000);
+        assert_eq!(map.post_order().len(), 5000);
+        assert_eq!(map.remove(&0), Some(()));
+    }
+}
@@ -1,18 +1,100 @@
-pub fn bubble_sort(arr: &mut [u32]) {
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use std::cmp::Ordering;
+
+pub fn bubble_sort<T: Ord>(arr: &mut [T]) {
+    bubble_sort_by(arr, T::cmp);
+}
+
+pub fn bubble_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    bubble_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn bubble_sort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    bubble_sort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
+
+pub fn bubble_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
     for i in (0..=arr.len()).rev() {
+        let mut swapped = false;
+
         for j in 1..i {
-            if arr[j - 1] > arr[j] {
-                let temp = arr[j];
-                arr[j] = arr[j - 1];
-                arr[j - 1] = temp;
+            // Only swapping strictly greater elements keeps equal ones in order (stable)
+            if arr.compare(j - 1, j, &mut cmp) == Ordering::Greater {
+                arr.swap(j - 1, j);
+                swapped = true;
+            }
+        }
+
+        // Nothing moved, so everything is in place already
+        if !swapped {
+            return;
+        }
+    }
+}
+
+pub fn cocktail_shaker_sort<T: Ord>(arr: &mut [T]) {
+    cocktail_shaker_sort_by(arr, T::cmp);
+}
+
+pub fn cocktail_shaker_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    cocktail_shaker_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+// Bubble sort going back and forth, so small elements at the end ("turtles")
+// move to the front in one pass instead of one step per pass
+pub fn cocktail_shaker_sort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    cocktail_shaker_sort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
+
+pub fn cocktail_shaker_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if arr.len() < 2 {
+        return;
+    }
+
+    let mut lo = 0;
+    let mut hi = arr.len() - 1;
+
+    while lo < hi {
+        // Everything after the last swap is sorted already
+        let mut forward_swap = lo;
+        for j in lo..hi {
+            if arr.compare(j, j + 1, &mut cmp) == Ordering::Greater {
+                arr.swap(j, j + 1);
+                forward_swap = j;
             }
         }
+        hi = forward_swap;
+
+        // Same on the way back, everything before the last swap is sorted
+        let mut backward_swap = hi;
+        for j in (lo..hi).rev() {
+            if arr.compare(j, j + 1, &mut cmp) == Ordering::Greater {
+                arr.swap(j, j + 1);
+                backward_swap = j + 1;
+            }
+        }
+        lo = backward_swap;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rng::XorShift;
 
     #[test]
     fn test() {
@@ -20,4 +102,114 @@ mod tests {
         bubble_sort(&mut arr);
         assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
     }
+
+    #[test]
+    fn generic() {
+        let mut arr = ["pear", "apple", "fig", "banana"];
+        bubble_sort(&mut arr);
+        assert_eq!(arr, ["apple", "banana", "fig", "pear"]);
+
+        let mut arr = [-3i64, 10, -20, 0];
+        bubble_sort_by(&mut arr, |a, b| b.cmp(a));
+        assert_eq!(arr, [10, 0, -3, -20]);
+
+        let mut arr = [-3i64, 10, -20, 0];
+        bubble_sort_by_key(&mut arr, |x| x.abs());
+        assert_eq!(arr, [0, -3, 10, -20]);
+
+        let mut empty: [u32; 0] = [];
+        bubble_sort(&mut empty);
+    }
+
+    #[test]
+    fn early_exit() {
+        let mut comparisons = 0;
+        let mut arr = [1, 2, 3, 4, 5, 6];
+        bubble_sort_by(&mut arr, |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        // A single pass over sorted input
+        assert_eq!(comparisons, 5);
+    }
+
+    #[test]
+    fn cocktail_shaker() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        cocktail_shaker_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = [2, 3, 4, 5, 1];
+        let mut comparisons = 0;
+        cocktail_shaker_sort_by(&mut arr, |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        assert_eq!(arr, [1, 2, 3, 4, 5]);
+        // The turtle gets carried all the way back in the first backward pass
+        assert_eq!(comparisons, 9);
+
+        let mut arr = ["ccc", "a", "bb"];
+        cocktail_shaker_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc"]);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Record {
+        key: u32,
+        id: usize,
+    }
+
+    fn records(rng: &mut XorShift, n: usize) -> Vec<Record> {
+        // Few distinct keys, so plenty of ties
+        (0..n)
+            .map(|id| Record {
+                key: rng.range(0, 5) as u32,
+                id,
+            })
+            .collect()
+    }
+
+    fn assert_stable(arr: &[Record]) {
+        for pair in arr.windows(2) {
+            assert!(pair[0].key <= pair[1].key);
+            if pair[0].key == pair[1].key {
+                assert!(pair[0].id < pair[1].id);
+            }
+        }
+    }
+
+    #[test]
+    fn stability() {
+        let mut rng = XorShift::new(31);
+        for n in 0..50 {
+            let original = records(&mut rng, n);
+
+            let mut arr = original.clone();
+            bubble_sort_by_key(&mut arr, |r| r.key);
+            assert_stable(&arr);
+
+            let mut arr = original.clone();
+            cocktail_shaker_sort_by_key(&mut arr, |r| r.key);
+            assert_stable(&arr);
+        }
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(32);
+        for n in 0..100 {
+            let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % 50).collect();
+            let mut expected = original.clone();
+            expected.sort();
+
+            let mut arr = original.clone();
+            bubble_sort(&mut arr);
+            assert_eq!(arr, expected);
+
+            let mut arr = original.clone();
+            cocktail_shaker_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
 }
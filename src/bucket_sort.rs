@@ -0,0 +1,148 @@
+use crate::counting_sort::{permute_tracked, positions};
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use std::cmp::Ordering;
+
+pub fn bucket_sort(arr: &mut [f64]) {
+    bucket_sort_by_key(arr, |x| *x);
+}
+
+// Spreads the keys over one bucket per element between the smallest and biggest key and
+// insertion sorts the buckets. Linear on average for roughly uniform keys. Infinities end
+// up in the first and last bucket and NaNs after everything else, like f64::total_cmp would
+// put positive NaNs.
+pub fn bucket_sort_by_key<T, F: FnMut(&T) -> f64>(arr: &mut [T], key: F) {
+    bucket_sort_tracked(Tracked::new(arr, &NoObserver), key);
+}
+
+pub fn bucket_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, mut key: F)
+where
+    F: FnMut(&T) -> f64,
+    O: Observer,
+{
+    let n = arr.len();
+    if n < 2 {
+        return;
+    }
+
+    let mut keys: Vec<f64> = (0..n).map(|i| key(arr.get(i))).collect();
+
+    let finite = keys.iter().filter(|k| k.is_finite());
+    let min = finite.clone().cloned().fold(f64::INFINITY, f64::min);
+    let max = finite.cloned().fold(f64::NEG_INFINITY, f64::max);
+    let width = (max - min) / n as f64;
+
+    // n regular buckets and one more at the end for NaNs
+    let bucket = |k: f64| -> usize {
+        if k.is_nan() {
+            n
+        } else if k == f64::NEG_INFINITY || width.is_nan() || width <= 0.0 {
+            0
+        } else if k == f64::INFINITY {
+            n - 1
+        } else {
+            (((k - min) / width) as usize).min(n - 1)
+        }
+    };
+
+    let buckets: Vec<u64> = keys.iter().map(|&k| bucket(k) as u64).collect();
+    let mut counts = vec![0; n + 1];
+    for &b in buckets.iter() {
+        counts[b as usize] += 1;
+    }
+
+    let mut dest = positions(&buckets, &mut counts.clone(), |b| b as usize);
+    permute_tracked(&mut arr, &mut keys, &mut dest);
+
+    let mut lo = 0;
+    for &count in counts.iter() {
+        insertion_sort_by_keys(arr.slice(lo..lo + count), &mut keys[lo..lo + count]);
+        lo += count;
+    }
+}
+
+fn insertion_sort_by_keys<T, O: Observer>(mut arr: Tracked<T, O>, keys: &mut [f64]) {
+    for i in 1..arr.len() {
+        let mut j = i;
+        // The keys move along with the elements, so they stand in for them
+        while j > 0
+            && arr.compare(j - 1, j, &mut |_, _| keys[j - 1].total_cmp(&keys[j]))
+                == Ordering::Greater
+        {
+            arr.swap(j - 1, j);
+            keys.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    #[test]
+    fn test() {
+        let mut arr = [0.42, 0.32, 0.23, 0.52, 0.25, 0.47, 0.51];
+        bucket_sort(&mut arr);
+        assert_eq!(arr, [0.23, 0.25, 0.32, 0.42, 0.47, 0.51, 0.52]);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(40);
+        for n in [0, 1, 2, 10, 1000] {
+            let mut arr: Vec<f64> = (0..n).map(|_| rng.next_f64() * 200.0 - 100.0).collect();
+            let mut expected = arr.clone();
+            expected.sort_by(f64::total_cmp);
+
+            bucket_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
+
+    #[test]
+    fn special_values() {
+        let mut arr = [
+            3.5,
+            f64::NAN,
+            f64::INFINITY,
+            -0.5,
+            f64::NEG_INFINITY,
+            3.5,
+            1e300,
+            -1e300,
+        ];
+        bucket_sort(&mut arr);
+        assert_eq!(
+            arr[..7],
+            [
+                f64::NEG_INFINITY,
+                -1e300,
+                -0.5,
+                3.5,
+                3.5,
+                1e300,
+                f64::INFINITY
+            ]
+        );
+        assert!(arr[7].is_nan());
+
+        let mut arr = [2.0, 2.0, 2.0];
+        bucket_sort(&mut arr);
+        assert_eq!(arr, [2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn records_by_key() {
+        let mut rng = XorShift::new(41);
+        // (score, original position) with a few distinct scores for ties
+        let mut arr: Vec<(f64, usize)> = (0..500)
+            .map(|i| (rng.range(0, 20) as f64 / 4.0, i))
+            .collect();
+        let mut expected = arr.clone();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        bucket_sort_by_key(&mut arr, |r| r.0);
+        assert_eq!(arr, expected);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use crate::radix_sort::{sort_radix_keys_tracked, RadixKey};
+
+// Counting sort gets a bucket per key in the range up to this many per element (or 256 for
+// tiny arrays), past that radix sort takes over
+const BUCKETS_PER_ELEMENT: u64 = 4;
+
+pub fn counting_sort<K: RadixKey>(arr: &mut [K]) {
+    counting_sort_by_key(arr, |x| *x);
+}
+
+// One bucket per possible key between the smallest and biggest one, so it only
+// makes sense when that range is not much bigger than the number of elements.
+// A wider range is handed to radix sort instead of allocating all those buckets.
+pub fn counting_sort_by_key<T, K: RadixKey, F: FnMut(&T) -> K>(arr: &mut [T], key: F) {
+    counting_sort_tracked(Tracked::new(arr, &NoObserver), key);
+}
+
+pub fn counting_sort_tracked<T, K, F, O>(mut arr: Tracked<T, O>, mut key: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+    O: Observer,
+{
+    if arr.len() < 2 {
+        return;
+    }
+
+    let mut keys: Vec<u64> = (0..arr.len()).map(|i| key(arr.get(i)).to_radix()).collect();
+    let min = *keys.iter().min().unwrap();
+    let max = *keys.iter().max().unwrap();
+
+    let limit = (arr.len() as u64)
+        .saturating_mul(BUCKETS_PER_ELEMENT)
+        .max(256);
+    let buckets = (max - min)
+        .checked_add(1)
+        .filter(|&buckets| buckets <= limit)
+        .and_then(|buckets| usize::try_from(buckets).ok());
+    let Some(buckets) = buckets else {
+        sort_radix_keys_tracked(&mut arr, keys, K::BYTES);
+        return;
+    };
+
+    let mut counts = vec![0; buckets];
+    for k in keys.iter() {
+        counts[(k - min) as usize] += 1;
+    }
+
+    let mut dest = positions(&keys, &mut counts, |k| (k - min) as usize);
+    permute_tracked(&mut arr, &mut keys, &mut dest);
+}
+
+// Turns bucket counts into where every element ends up. Elements in the same
+// bucket keep their order, which is what makes all the sorts built on this stable.
+pub fn positions<B>(keys: &[u64], counts: &mut [usize], mut bucket: B) -> Vec<usize>
+where
+    B: FnMut(u64) -> usize,
+{
+    let mut sum = 0;
+    for count in counts.iter_mut() {
+        let c = *count;
+        *count = sum;
+        sum += c;
+    }
+
+    keys.iter()
+        .map(|&k| {
+            let b = bucket(k);
+            counts[b] += 1;
+            counts[b] - 1
+        })
+        .collect()
+}
+
+pub fn permute<T, K>(arr: &mut [T], keys: &mut [K], dest: &mut [usize]) {
+    permute_tracked(&mut Tracked::new(arr, &NoObserver), keys, dest);
+}
+
+// Moves arr[i] (and keys[i] along with it) to dest[i] by following the cycles of the
+// permutation, so it's all swaps and T doesn't have to be Clone. Leaves dest as 0..n.
+pub fn permute_tracked<T, K, O>(arr: &mut Tracked<T, O>, keys: &mut [K], dest: &mut [usize])
+where
+    O: Observer,
+{
+    for i in 0..arr.len() {
+        while dest[i] != i {
+            let d = dest[i];
+            arr.swap(i, d);
+            keys.swap(i, d);
+            dest.swap(i, d);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    #[test]
+    fn test() {
+        let mut arr = [9u32, 3, 7, 4, 69, 420, 42];
+        counting_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = [3i8, -128, 127, 0, -1];
+        counting_sort(&mut arr);
+        assert_eq!(arr, [-128, -1, 0, 3, 127]);
+    }
+
+    #[test]
+    fn permutation() {
+        let mut arr = ['a', 'b', 'c', 'd'];
+        let mut keys = [0, 1, 2, 3];
+        let mut dest = [2, 0, 3, 1];
+        permute(&mut arr, &mut keys, &mut dest);
+        assert_eq!(arr, ['b', 'd', 'a', 'c']);
+        assert_eq!(keys, [1, 3, 0, 2]);
+        assert_eq!(dest, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn records_by_key() {
+        let mut rng = XorShift::new(36);
+        // (key, original position), no Clone needed for the sorted type
+        let mut arr: Vec<(i32, usize)> = (0..1000)
+            .map(|i| (rng.range(0, 50) as i32 - 25, i))
+            .collect();
+        let mut expected = arr.clone();
+        expected.sort();
+
+        counting_sort_by_key(&mut arr, |r| r.0);
+        // Stable, so ties are still in their original order
+        assert_eq!(arr, expected);
+    }
+
+    #[test]
+    fn wide_range() {
+        // Would be 2^64 or 2^40 buckets, radix sort does these
+        let mut arr = [u64::MAX, 0, 5, u64::MAX - 1, 0];
+        counting_sort(&mut arr);
+        assert_eq!(arr, [0, 0, 5, u64::MAX - 1, u64::MAX]);
+
+        let mut arr = [i64::MIN, i64::MAX, -1, 0];
+        counting_sort(&mut arr);
+        assert_eq!(arr, [i64::MIN, -1, 0, i64::MAX]);
+
+        let mut rng = XorShift::new(37);
+        let mut arr: Vec<(u64, usize)> = (0..1000)
+            .map(|i| ((rng.range(0, 8) as u64) << 40, i))
+            .collect();
+        let mut expected = arr.clone();
+        expected.sort();
+        counting_sort_by_key(&mut arr, |r| r.0);
+        assert_eq!(arr, expected);
+    }
+}
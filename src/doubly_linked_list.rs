@@ -52,7 +52,7 @@ impl<T: Clone> DoublyLinkedList<T> {
             return;
         }
 
-        new_node.borrow_mut().next = Some(Rc::clone(&self.head.as_ref().unwrap()));
+        new_node.borrow_mut().next = Some(Rc::clone(self.head.as_ref().unwrap()));
         self.head.as_ref().unwrap().borrow_mut().prev = Some(Rc::clone(&new_node));
         self.head = Some(Rc::clone(&new_node));
     }
@@ -68,25 +68,17 @@ impl<T: Clone> DoublyLinkedList<T> {
             return;
         }
 
-        new_node.borrow_mut().prev = Some(Rc::clone(&self.tail.as_ref().unwrap()));
+        new_node.borrow_mut().prev = Some(Rc::clone(self.tail.as_ref().unwrap()));
         self.tail.as_ref().unwrap().borrow_mut().next = Some(Rc::clone(&new_node));
         self.tail = Some(Rc::clone(&new_node));
     }
 
     pub fn peek_head(&self) -> Option<T> {
-        if self.head.is_none() {
-            return None;
-        }
-
-        Some(self.head.as_ref().unwrap().borrow().value.clone())
+        self.head.as_ref().map(|head| head.borrow().value.clone())
     }
 
     pub fn peek_tail(&self) -> Option<T> {
-        if self.tail.is_none() {
-            return None;
-        }
-
-        Some(self.tail.as_ref().unwrap().borrow().value.clone())
+        self.tail.as_ref().map(|tail| tail.borrow().value.clone())
     }
 
     pub fn insert_after(&mut self, pos: u32, value: T) {
@@ -124,6 +116,12 @@ impl<T: Clone> DoublyLinkedList<T> {
     }
 }
 
+impl<T: Clone> Default for DoublyLinkedList<T> {
+    fn default() -> Self {
+        DoublyLinkedList::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::doubly_linked_list::DoublyLinkedList;
@@ -0,0 +1,442 @@
+use crate::linear_search::find_all_by;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+
+// All of these work on slices, so bytes for ASCII and a Vec<char> for anything else.
+// The _within versions give up as soon as the distance is sure to be over max, which is
+// most of the time when looking for typos in a dictionary.
+
+// Edits are inserting, deleting or replacing one element
+pub fn levenshtein<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    levenshtein_within(a, b, usize::MAX).unwrap()
+}
+
+pub fn levenshtein_within<T: PartialEq>(a: &[T], b: &[T], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // Rows as long as the shorter one. Only cells within max of the diagonal can be within
+    // max at all, so each row just fills that band and everything outside it counts as far.
+    let (a, b) = if a.len() < b.len() { (b, a) } else { (a, b) };
+    let far = max.saturating_add(1);
+    let mut prev: Vec<usize> = (0..=b.len()).map(|j| j.min(far)).collect();
+    let mut row = vec![far; b.len() + 1];
+
+    for i in 1..=a.len() {
+        let lo = i.saturating_sub(max).max(1);
+        let hi = i.saturating_add(max).min(b.len());
+
+        // Left of the band, the next row reads it as its diagonal
+        row[lo - 1] = if lo == 1 { i.min(far) } else { far };
+        let mut least = row[lo - 1];
+
+        for j in lo..=hi {
+            let replace = prev[j - 1].saturating_add(usize::from(a[i - 1] != b[j - 1]));
+            row[j] = replace
+                .min(prev[j].saturating_add(1))
+                .min(row[j - 1].saturating_add(1))
+                .min(far);
+            least = least.min(row[j]);
+        }
+        // Right of the band, the next row reads it from above
+        if hi < b.len() {
+            row[hi + 1] = far;
+        }
+
+        // Every row is at least as far as the one before, so this only goes up
+        if least > max {
+            return None;
+        }
+        mem::swap(&mut prev, &mut row);
+    }
+
+    let distance = prev[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+// Levenshtein plus swapping two neighbours, "teh" is one edit from "the". This is the
+// real one and not the optimal string alignment shortcut, which can't edit a swapped pair
+// again ("ca" -> "abc" is 2 here, 3 there) and isn't a metric, so no good for a BkTree.
+pub fn damerau_levenshtein<T: Eq + Hash>(a: &[T], b: &[T]) -> usize {
+    damerau_levenshtein_within(a, b, usize::MAX).unwrap()
+}
+
+pub fn damerau_levenshtein_within<T: Eq + Hash>(a: &[T], b: &[T], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // Lowrance-Wagner. d[i + 1][j + 1] is the distance between a[..i] and b[..j], with an
+    // extra row and column of "too far" so a swap with nothing before it never wins.
+    let (n, m) = (a.len(), b.len());
+    let width = m + 2;
+    let far = n + m + 1;
+    let mut d = vec![0; (n + 2) * width];
+    d[0] = far;
+    for i in 0..=n {
+        d[(i + 1) * width] = far;
+        d[(i + 1) * width + 1] = i;
+    }
+    for j in 0..=m {
+        d[j + 1] = far;
+        d[width + j + 1] = j;
+    }
+
+    // Last row of a that each element was seen in
+    let mut last_row: HashMap<&T, usize> = HashMap::new();
+    // A swap can reach back any number of rows, but it pays a deletion for every one it
+    // skips, so the best anything further down can do is this
+    let mut reachable = 0;
+
+    for i in 1..=n {
+        // Last column of b in this row that matched a[i - 1]
+        let mut last_col = 0;
+        let mut least = far;
+
+        for j in 1..=m {
+            let k = last_row.get(&b[j - 1]).copied().unwrap_or(0);
+            let l = last_col;
+            let cost = if a[i - 1] == b[j - 1] {
+                last_col = j;
+                0
+            } else {
+                1
+            };
+
+            let swap = d[k * width + l] + (i - k - 1) + 1 + (j - l - 1);
+            let best = (d[i * width + j] + cost)
+                .min(d[(i + 1) * width + j] + 1)
+                .min(d[i * width + j + 1] + 1)
+                .min(swap);
+            d[(i + 1) * width + j + 1] = best;
+            least = least.min(best);
+        }
+
+        last_row.insert(&a[i - 1], i);
+        reachable = (reachable + 1).min(least.min(i));
+        if reachable > max {
+            return None;
+        }
+    }
+
+    let distance = d[(n + 1) * width + m + 1];
+    (distance <= max).then_some(distance)
+}
+
+// Only replacing, so both have to be the same length
+pub fn hamming<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    if a.len() != b.len() {
+        panic!("Need equal lengths");
+    }
+
+    hamming_within(a, b, usize::MAX).unwrap()
+}
+
+// None for different lengths too, they're not within any distance
+pub fn hamming_within<T: PartialEq>(a: &[T], b: &[T], max: usize) -> Option<usize> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    let mut distance = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        if x != y {
+            distance += 1;
+            if distance > max {
+                return None;
+            }
+        }
+    }
+
+    Some(distance)
+}
+
+// Positions of all the words at most max edits away from the needle, checking every one
+pub fn fuzzy_find_all<I>(haystack: I, needle: &str, max: usize) -> Vec<usize>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let needle: Vec<char> = needle.chars().collect();
+    find_all_by(haystack, |word| {
+        let word: Vec<char> = word.as_ref().chars().collect();
+        levenshtein_within(&word, &needle, max).is_some()
+    })
+}
+
+// Burkhard-Keller tree. Every child hangs off its parent by their distance, and the
+// triangle inequality says a word within k of the query can only be under the children
+// d - k..=d + k of a node that's d away. That skips most of the tree for small k. distance
+// has to be a metric, so Levenshtein, Damerau-Levenshtein or Hamming for equal lengths.
+pub struct BkTree<T, D> {
+    nodes: Vec<BkNode<T>>,
+    distance: D,
+}
+
+struct BkNode<T> {
+    item: T,
+    children: Vec<(usize, usize)>, // (distance, node)
+}
+
+impl<T, D> BkTree<T, D>
+where
+    D: Fn(&T, &T) -> usize,
+{
+    pub fn new(distance: D) -> BkTree<T, D> {
+        BkTree {
+            nodes: Vec::new(),
+            distance,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // False if it was there already
+    pub fn insert(&mut self, item: T) -> bool {
+        if self.nodes.is_empty() {
+            self.nodes.push(BkNode {
+                item,
+                children: Vec::new(),
+            });
+            return true;
+        }
+
+        let mut node = 0;
+        loop {
+            let d = (self.distance)(&item, &self.nodes[node].item);
+            if d == 0 {
+                return false;
+            }
+
+            match self.nodes[node].children.iter().find(|&&(cd, _)| cd == d) {
+                Some(&(_, child)) => node = child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(BkNode {
+                        item,
+                        children: Vec::new(),
+                    });
+                    self.nodes[node].children.push((d, child));
+                    return true;
+                }
+            }
+        }
+    }
+
+    // Everything at most k away, closest first
+    pub fn find(&self, query: &T, k: usize) -> Vec<(usize, &T)> {
+        let mut found = Vec::new();
+        if self.nodes.is_empty() {
+            return found;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let d = (self.distance)(query, &node.item);
+            if d <= k {
+                found.push((d, &node.item));
+            }
+
+            for &(cd, child) in node.children.iter() {
+                if cd.abs_diff(d) <= k {
+                    stack.push(child);
+                }
+            }
+        }
+
+        found.sort_by_key(|&(d, _)| d);
+        found
+    }
+}
+
+impl<T, D> Extend<T> for BkTree<T, D>
+where
+    D: Fn(&T, &T) -> usize,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    fn random(rng: &mut XorShift, len: usize) -> Vec<u8> {
+        (0..len).map(|_| b'a' + rng.range(0, 4) as u8).collect()
+    }
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(levenshtein(b"kitten", b"sitting"), 3);
+        assert_eq!(levenshtein(b"", b"abc"), 3);
+        assert_eq!(levenshtein(b"flaw", b"lawn"), 2);
+        assert_eq!(levenshtein(&chars("größe"), &chars("grösse")), 2);
+
+        assert_eq!(damerau_levenshtein(b"teh", b"the"), 1);
+        assert_eq!(levenshtein(b"teh", b"the"), 2);
+        assert_eq!(damerau_levenshtein(b"ca", b"abc"), 2);
+        assert_eq!(damerau_levenshtein(b"abcdef", b"badcfe"), 3);
+        assert_eq!(damerau_levenshtein(b"", b""), 0);
+
+        assert_eq!(hamming(b"karolin", b"kathrin"), 3);
+        assert_eq!(hamming_within(b"karolin", b"kathrin", 2), None);
+        assert_eq!(hamming_within(b"karolin", b"kathrin", 3), Some(3));
+        assert_eq!(hamming_within(b"karolin", b"kathri", 10), None);
+    }
+
+    #[test]
+    fn within() {
+        assert_eq!(levenshtein_within(b"kitten", b"sitting", 2), None);
+        assert_eq!(levenshtein_within(b"kitten", b"sitting", 3), Some(3));
+        assert_eq!(levenshtein_within(b"a", b"abcdefgh", 3), None);
+        assert_eq!(damerau_levenshtein_within(b"teh", b"the", 0), None);
+        assert_eq!(damerau_levenshtein_within(b"teh", b"the", 1), Some(1));
+
+        let mut rng = XorShift::new(48);
+        for _ in 0..2000 {
+            let (la, lb) = (rng.range(0, 16), rng.range(0, 16));
+            let a = random(&mut rng, la);
+            let b = random(&mut rng, lb);
+            let max = rng.range(0, 6);
+
+            let lev = levenshtein(&a, &b);
+            assert_eq!(
+                levenshtein_within(&a, &b, max),
+                Some(lev).filter(|&d| d <= max)
+            );
+
+            let dl = damerau_levenshtein(&a, &b);
+            assert!(dl <= lev);
+            assert_eq!(dl, damerau_levenshtein(&b, &a));
+            assert_eq!(
+                damerau_levenshtein_within(&a, &b, max),
+                Some(dl).filter(|&d| d <= max),
+                "{:?} {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn triangle() {
+        // What the BkTree relies on
+        let mut rng = XorShift::new(49);
+        for _ in 0..2000 {
+            let words: Vec<Vec<u8>> = (0..3)
+                .map(|_| {
+                    let len = rng.range(0, 7);
+                    random(&mut rng, len)
+                })
+                .collect();
+            let (a, b, c) = (&words[0], &words[1], &words[2]);
+
+            assert!(levenshtein(a, c) <= levenshtein(a, b) + levenshtein(b, c));
+            assert!(
+                damerau_levenshtein(a, c) <= damerau_levenshtein(a, b) + damerau_levenshtein(b, c)
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Need equal lengths")]
+    fn hamming_lengths() {
+        hamming(b"abc", b"ab");
+    }
+
+    #[test]
+    fn fuzzy_find() {
+        let words = ["apple", "appel", "ample", "maple", "apply", "banana"];
+        assert_eq!(fuzzy_find_all(words, "aple", 1), [0, 2, 3]);
+        assert_eq!(fuzzy_find_all(&words, "banan", 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn bk_tree() {
+        let mut tree =
+            BkTree::new(|a: &String, b: &String| levenshtein(a.as_bytes(), b.as_bytes()));
+        for word in [
+            "book", "books", "cake", "boo", "boon", "cook", "cape", "cart",
+        ] {
+            assert!(tree.insert(word.to_string()));
+        }
+        assert!(!tree.insert("cook".to_string()));
+        assert_eq!(tree.len(), 8);
+
+        let found: Vec<(usize, &str)> = tree
+            .find(&"bool".to_string(), 1)
+            .into_iter()
+            .map(|(d, w)| (d, w.as_str()))
+            .collect();
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|&(d, _)| d == 1));
+
+        let found = tree.find(&"cape".to_string(), 0);
+        assert_eq!(found, [(0, &"cape".to_string())]);
+
+        let empty = BkTree::new(|a: &String, b: &String| levenshtein(a.as_bytes(), b.as_bytes()));
+        assert!(empty.find(&"x".to_string(), 3).is_empty());
+    }
+
+    #[test]
+    fn bk_tree_against_linear() {
+        let mut rng = XorShift::new(50);
+        let words: Vec<String> = (0..500)
+            .map(|_| {
+                let len = rng.range(1, 8);
+                String::from_utf8(random(&mut rng, len)).unwrap()
+            })
+            .collect();
+
+        let mut unique = words.clone();
+        unique.sort();
+        unique.dedup();
+
+        let mut lev = BkTree::new(|a: &String, b: &String| levenshtein(a.as_bytes(), b.as_bytes()));
+        lev.extend(words.iter().cloned());
+        assert_eq!(lev.len(), unique.len());
+
+        let mut dl =
+            BkTree::new(|a: &String, b: &String| damerau_levenshtein(a.as_bytes(), b.as_bytes()));
+        dl.extend(words.iter().cloned());
+
+        for _ in 0..100 {
+            let len = rng.range(1, 8);
+            let query = String::from_utf8(random(&mut rng, len)).unwrap();
+            let k = rng.range(0, 3);
+
+            let mut found: Vec<&String> = lev.find(&query, k).into_iter().map(|(_, w)| w).collect();
+            found.sort();
+            let expected: Vec<&String> = fuzzy_find_all(&unique, &query, k)
+                .into_iter()
+                .map(|i| &unique[i])
+                .collect();
+            assert_eq!(found, expected);
+
+            let mut found: Vec<&String> = dl.find(&query, k).into_iter().map(|(_, w)| w).collect();
+            found.sort();
+            let expected: Vec<&String> = unique
+                .iter()
+                .filter(|w| damerau_levenshtein_within(w.as_bytes(), query.as_bytes(), k).is_some())
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+}
@@ -0,0 +1,685 @@
+use crate::stack::Stack;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: usize, // byte offset in the source
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rpn {
+    Number(f64),
+    Variable(String),
+    Neg,
+    Binary(char),
+    Call(String, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpnToken {
+    pub kind: Rpn,
+    pub pos: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    UnexpectedChar(char),
+    InvalidNumber,
+    UnexpectedToken,
+    UnexpectedEnd,
+    UnmatchedParen,
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArgumentCount { name: String, found: usize },
+    DivisionByZero,
+    MalformedRpn, // hand built RPN that leaves too few or too many values on the stack
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    pub kind: ErrorKind,
+    pub pos: usize,
+}
+
+impl ExprError {
+    fn new(kind: ErrorKind, pos: usize) -> ExprError {
+        ExprError { kind, pos }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c)?,
+            ErrorKind::InvalidNumber => write!(f, "invalid number")?,
+            ErrorKind::UnexpectedToken => write!(f, "unexpected token")?,
+            ErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression")?,
+            ErrorKind::UnmatchedParen => write!(f, "unmatched parenthesis")?,
+            ErrorKind::UnknownVariable(name) => write!(f, "unknown variable '{}'", name)?,
+            ErrorKind::UnknownFunction(name) => write!(f, "unknown function '{}'", name)?,
+            ErrorKind::WrongArgumentCount { name, found } => {
+                write!(f, "wrong number of arguments for '{}': {}", name, found)?
+            }
+            ErrorKind::DivisionByZero => write!(f, "division by zero")?,
+            ErrorKind::MalformedRpn => write!(f, "malformed RPN")?,
+        }
+
+        write!(f, " at position {}", self.pos)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ExprError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let pos = i;
+
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == b'.' {
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            // Exponent part, like 1e-3
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    while j < bytes.len() && bytes[j].is_ascii_digit() {
+                        j += 1;
+                    }
+                    i = j;
+                }
+            }
+
+            let value = src[pos..i]
+                .parse::<f64>()
+                .map_err(|_| ExprError::new(ErrorKind::InvalidNumber, pos))?;
+            tokens.push(Token {
+                kind: TokenKind::Number(value),
+                pos,
+            });
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Ident(src[pos..i].to_string()),
+                pos,
+            });
+            continue;
+        }
+
+        let kind = match c {
+            b'+' | b'-' | b'*' | b'/' | b'%' | b'^' => TokenKind::Op(c as char),
+            b'(' => TokenKind::LParen,
+            b')' => TokenKind::RParen,
+            b',' => TokenKind::Comma,
+            _ => {
+                // Reporting the whole char, not just its first byte
+                let c = src[pos..].chars().next().unwrap();
+                return Err(ExprError::new(ErrorKind::UnexpectedChar(c), pos));
+            }
+        };
+        tokens.push(Token { kind, pos });
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone)]
+enum Pending {
+    Op { op: char, pos: usize },
+    Neg { pos: usize },
+    Paren { pos: usize },
+    Call { name: String, pos: usize },
+}
+
+fn precedence(op: char) -> u8 {
+    match op {
+        '+' | '-' => 1,
+        '*' | '/' | '%' => 2,
+        '^' => 4,
+        _ => unreachable!("Unknown operator"),
+    }
+}
+
+// Unary minus binds tighter than * but looser than ^, so -2^2 is -(2^2)
+const NEG_PRECEDENCE: u8 = 3;
+
+fn arity_ok(name: &str, args: usize) -> Option<bool> {
+    match name {
+        "abs" | "sqrt" | "floor" | "ceil" | "round" => Some(args == 1),
+        "pow" => Some(args == 2),
+        "min" | "max" => Some(args >= 1),
+        _ => None,
+    }
+}
+
+// Shunting-yard: operands go straight to the output, operators wait on the stack
+// until something with lower precedence (or a closing paren) pushes them out
+pub fn to_rpn(tokens: &[Token]) -> Result<Vec<RpnToken>, ExprError> {
+    let mut output = Vec::new();
+    let mut ops: Stack<Pending> = Stack::new();
+    let mut args: Stack<usize> = Stack::new(); // commas seen in each open call
+    let mut expect_operand = true;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let Token { kind, pos } = &tokens[i];
+        let pos = *pos;
+
+        match kind {
+            TokenKind::Number(_) | TokenKind::Ident(_) if !expect_operand => {
+                return Err(ExprError::new(ErrorKind::UnexpectedToken, pos));
+            }
+            TokenKind::Number(value) => {
+                output.push(RpnToken {
+                    kind: Rpn::Number(*value),
+                    pos,
+                });
+                expect_operand = false;
+            }
+            TokenKind::Ident(name) => {
+                let is_call = matches!(
+                    tokens.get(i + 1),
+                    Some(Token {
+                        kind: TokenKind::LParen,
+                        ..
+                    })
+                );
+
+                if is_call {
+                    if arity_ok(name, 0).is_none() {
+                        return Err(ExprError::new(
+                            ErrorKind::UnknownFunction(name.clone()),
+                            pos,
+                        ));
+                    }
+                    ops.push(Pending::Call {
+                        name: name.clone(),
+                        pos,
+                    });
+                    args.push(0);
+                    i += 1; // the paren is part of the call
+                } else {
+                    output.push(RpnToken {
+                        kind: Rpn::Variable(name.clone()),
+                        pos,
+                    });
+                    expect_operand = false;
+                }
+            }
+            TokenKind::Op('-') if expect_operand => ops.push(Pending::Neg { pos }),
+            TokenKind::Op('+') if expect_operand => {} // unary plus does nothing
+            TokenKind::Op(_) if expect_operand => {
+                return Err(ExprError::new(ErrorKind::UnexpectedToken, pos));
+            }
+            TokenKind::Op(op) => {
+                let prec = precedence(*op);
+                let right_assoc = *op == '^';
+
+                while let Some(top) = ops.peek() {
+                    let top_prec = match top {
+                        Pending::Op { op, .. } => precedence(op),
+                        Pending::Neg { .. } => NEG_PRECEDENCE,
+                        _ => break,
+                    };
+                    if top_prec < prec || (top_prec == prec && right_assoc) {
+                        break;
+                    }
+                    output.push(pending_to_rpn(ops.pop().unwrap()));
+                }
+
+                ops.push(Pending::Op { op: *op, pos });
+                expect_operand = true;
+            }
+            TokenKind::LParen if !expect_operand => {
+                return Err(ExprError::new(ErrorKind::UnexpectedToken, pos));
+            }
+            TokenKind::LParen => ops.push(Pending::Paren { pos }),
+            TokenKind::Comma => {
+                if expect_operand {
+                    return Err(ExprError::new(ErrorKind::UnexpectedToken, pos));
+                }
+                pop_until_paren(&mut ops, &mut output);
+                match ops.peek() {
+                    Some(Pending::Call { .. }) => {
+                        let commas = args.pop().unwrap();
+                        args.push(commas + 1);
+                    }
+                    _ => return Err(ExprError::new(ErrorKind::UnexpectedToken, pos)),
+                }
+                expect_operand = true;
+            }
+            TokenKind::RParen => {
+                // Only an empty call like f() gets to close right after opening
+                let empty_call = expect_operand
+                    && i > 0
+                    && tokens[i - 1].kind == TokenKind::LParen
+                    && matches!(ops.peek(), Some(Pending::Call { .. }));
+                if expect_operand && !empty_call {
+                    return Err(ExprError::new(ErrorKind::UnexpectedToken, pos));
+                }
+
+                pop_until_paren(&mut ops, &mut output);
+                match ops.pop() {
+                    Some(Pending::Paren { .. }) => {}
+                    Some(Pending::Call { name, pos }) => {
+                        let commas = args.pop().unwrap();
+                        let count = if empty_call { 0 } else { commas + 1 };
+                        if arity_ok(&name, count) != Some(true) {
+                            return Err(ExprError::new(
+                                ErrorKind::WrongArgumentCount { name, found: count },
+                                pos,
+                            ));
+                        }
+                        output.push(RpnToken {
+                            kind: Rpn::Call(name, count),
+                            pos,
+                        });
+                    }
+                    _ => return Err(ExprError::new(ErrorKind::UnmatchedParen, pos)),
+                }
+                expect_operand = false;
+            }
+        }
+
+        i += 1;
+    }
+
+    if expect_operand {
+        let end = tokens.last().map_or(0, |t| t.pos + 1);
+        return Err(ExprError::new(ErrorKind::UnexpectedEnd, end));
+    }
+
+    while let Some(pending) = ops.pop() {
+        match pending {
+            Pending::Paren { pos } | Pending::Call { pos, .. } => {
+                return Err(ExprError::new(ErrorKind::UnmatchedParen, pos));
+            }
+            pending => output.push(pending_to_rpn(pending)),
+        }
+    }
+
+    Ok(output)
+}
+
+fn pop_until_paren(ops: &mut Stack<Pending>, output: &mut Vec<RpnToken>) {
+    while let Some(top) = ops.peek() {
+        if matches!(top, Pending::Paren { .. } | Pending::Call { .. }) {
+            break;
+        }
+        output.push(pending_to_rpn(ops.pop().unwrap()));
+    }
+}
+
+fn pending_to_rpn(pending: Pending) -> RpnToken {
+    match pending {
+        Pending::Op { op, pos } => RpnToken {
+            kind: Rpn::Binary(op),
+            pos,
+        },
+        Pending::Neg { pos } => RpnToken {
+            kind: Rpn::Neg,
+            pos,
+        },
+        _ => unreachable!("Parens never make it to the output"),
+    }
+}
+
+// The RPN doesn't have to come from to_rpn, so nothing about it is taken for granted
+pub fn evaluate_rpn(rpn: &[RpnToken], vars: &HashMap<String, f64>) -> Result<f64, ExprError> {
+    let mut values: Vec<f64> = Vec::new();
+
+    for token in rpn {
+        let malformed = || ExprError::new(ErrorKind::MalformedRpn, token.pos);
+
+        let value = match &token.kind {
+            Rpn::Number(value) => *value,
+            Rpn::Variable(name) => match vars.get(name) {
+                Some(value) => *value,
+                None => {
+                    return Err(ExprError::new(
+                        ErrorKind::UnknownVariable(name.clone()),
+                        token.pos,
+                    ))
+                }
+            },
+            Rpn::Neg => -values.pop().ok_or_else(malformed)?,
+            Rpn::Binary(op) => {
+                let b = values.pop().ok_or_else(malformed)?;
+                let a = values.pop().ok_or_else(malformed)?;
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' | '%' if b == 0.0 => {
+                        return Err(ExprError::new(ErrorKind::DivisionByZero, token.pos))
+                    }
+                    '/' => a / b,
+                    '%' => a % b,
+                    '^' => a.powf(b),
+                    _ => return Err(malformed()),
+                }
+            }
+            Rpn::Call(name, count) => {
+                match arity_ok(name, *count) {
+                    Some(true) => {}
+                    Some(false) => {
+                        return Err(ExprError::new(
+                            ErrorKind::WrongArgumentCount {
+                                name: name.clone(),
+                                found: *count,
+                            },
+                            token.pos,
+                        ))
+                    }
+                    None => {
+                        return Err(ExprError::new(
+                            ErrorKind::UnknownFunction(name.clone()),
+                            token.pos,
+                        ))
+                    }
+                }
+
+                // A hand built count can be anything, so check it before taking the arguments
+                if *count > values.len() {
+                    return Err(malformed());
+                }
+                let call_args = values.split_off(values.len() - count);
+                call(name, &call_args)
+            }
+        };
+
+        values.push(value);
+    }
+
+    // Exactly one value left, the result
+    let end = rpn.last().map_or(0, |token| token.pos);
+    match (values.pop(), values.is_empty()) {
+        (Some(value), true) => Ok(value),
+        _ => Err(ExprError::new(ErrorKind::MalformedRpn, end)),
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> f64 {
+    match name {
+        "abs" => args[0].abs(),
+        "sqrt" => args[0].sqrt(),
+        "floor" => args[0].floor(),
+        "ceil" => args[0].ceil(),
+        "round" => args[0].round(),
+        "pow" => args[0].powf(args[1]),
+        "min" => args.iter().cloned().fold(f64::INFINITY, f64::min),
+        "max" => args.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        _ => unreachable!("evaluate_rpn checks the name and arity first"),
+    }
+}
+
+pub struct Expression {
+    rpn: Vec<RpnToken>,
+}
+
+impl Expression {
+    pub fn parse(src: &str) -> Result<Expression, ExprError> {
+        let tokens = tokenize(src)?;
+        Ok(Expression {
+            rpn: to_rpn(&tokens)?,
+        })
+    }
+
+    pub fn rpn(&self) -> &[RpnToken] {
+        &self.rpn
+    }
+
+    pub fn evaluate(&self, vars: &HashMap<String, f64>) -> Result<f64, ExprError> {
+        evaluate_rpn(&self.rpn, vars)
+    }
+}
+
+pub fn evaluate(src: &str, vars: &HashMap<String, f64>) -> Result<f64, ExprError> {
+    Expression::parse(src)?.evaluate(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(src: &str) -> Result<f64, ExprError> {
+        evaluate(src, &HashMap::new())
+    }
+
+    fn err(src: &str) -> (ErrorKind, usize) {
+        let e = eval(src).unwrap_err();
+        (e.kind, e.pos)
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = tokenize("max(x1, 2.5e1)").unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident("max".to_string()),
+                TokenKind::LParen,
+                TokenKind::Ident("x1".to_string()),
+                TokenKind::Comma,
+                TokenKind::Number(25.0),
+                TokenKind::RParen,
+            ]
+        );
+        assert_eq!(tokens[4].pos, 8);
+    }
+
+    #[test]
+    fn rpn() {
+        let expr = Expression::parse("1 + 2 * 3 - x").unwrap();
+        let kinds: Vec<Rpn> = expr.rpn().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Rpn::Number(1.0),
+                Rpn::Number(2.0),
+                Rpn::Number(3.0),
+                Rpn::Binary('*'),
+                Rpn::Binary('+'),
+                Rpn::Variable("x".to_string()),
+                Rpn::Binary('-'),
+            ]
+        );
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(eval("8 - 3 - 2"), Ok(3.0));
+        assert_eq!(eval("16 / 4 / 2"), Ok(2.0));
+        assert_eq!(eval("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(eval("7 % 4 * 2"), Ok(6.0));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-3"), Ok(-3.0));
+        assert_eq!(eval("--3"), Ok(3.0));
+        assert_eq!(eval("2 * -3"), Ok(-6.0));
+        assert_eq!(eval("-2 ^ 2"), Ok(-4.0));
+        assert_eq!(eval("2 ^ -1"), Ok(0.5));
+        assert_eq!(eval("-(1 + 2) * 2"), Ok(-6.0));
+        assert_eq!(eval("+4 - -4"), Ok(8.0));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("max(1, 2)"), Ok(2.0));
+        assert_eq!(eval("min(4, 2, 8) + 1"), Ok(3.0));
+        assert_eq!(eval("max(1, min(5, 3) * 2) ^ 2"), Ok(36.0));
+        assert_eq!(eval("abs(-2) + sqrt(16)"), Ok(6.0));
+        assert_eq!(eval("pow(2, 10)"), Ok(1024.0));
+        assert_eq!(eval("-max(1, -2)"), Ok(-1.0));
+    }
+
+    #[test]
+    fn variables() {
+        let mut vars = HashMap::new();
+        vars.insert("a".to_string(), 3.0);
+        vars.insert("b_2".to_string(), 4.0);
+        let expr = Expression::parse("sqrt(a ^ 2 + b_2 ^ 2)").unwrap();
+        assert_eq!(expr.evaluate(&vars), Ok(5.0));
+
+        vars.insert("a".to_string(), 0.0);
+        assert_eq!(expr.evaluate(&vars), Ok(4.0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(err("1 + $"), (ErrorKind::UnexpectedChar('$'), 4));
+        assert_eq!(err("1..2"), (ErrorKind::InvalidNumber, 0));
+        assert_eq!(err(""), (ErrorKind::UnexpectedEnd, 0));
+        assert_eq!(err("1 +"), (ErrorKind::UnexpectedEnd, 3));
+        assert_eq!(err("1 2"), (ErrorKind::UnexpectedToken, 2));
+        assert_eq!(err("* 2"), (ErrorKind::UnexpectedToken, 0));
+        assert_eq!(err("2 (3)"), (ErrorKind::UnexpectedToken, 2));
+        assert_eq!(err("(1 + 2"), (ErrorKind::UnmatchedParen, 0));
+        assert_eq!(err("1 + 2)"), (ErrorKind::UnmatchedParen, 5));
+        assert_eq!(err("()"), (ErrorKind::UnexpectedToken, 1));
+        assert_eq!(err("1, 2"), (ErrorKind::UnexpectedToken, 1));
+        assert_eq!(err("(1, 2)"), (ErrorKind::UnexpectedToken, 2));
+        assert_eq!(err("max(1,)"), (ErrorKind::UnexpectedToken, 6));
+        assert_eq!(err("max(1"), (ErrorKind::UnmatchedParen, 0));
+        assert_eq!(
+            err("foo(1)"),
+            (ErrorKind::UnknownFunction("foo".to_string()), 0)
+        );
+        assert_eq!(
+            err("1 + abs(1, 2)"),
+            (
+                ErrorKind::WrongArgumentCount {
+                    name: "abs".to_string(),
+                    found: 2
+                },
+                4
+            )
+        );
+        assert_eq!(
+            err("max()"),
+            (
+                ErrorKind::WrongArgumentCount {
+                    name: "max".to_string(),
+                    found: 0
+                },
+                0
+            )
+        );
+        assert_eq!(
+            err("x * 2"),
+            (ErrorKind::UnknownVariable("x".to_string()), 0)
+        );
+        assert_eq!(err("1 / (2 - 2)"), (ErrorKind::DivisionByZero, 2));
+        assert_eq!(err("1 % 0"), (ErrorKind::DivisionByZero, 2));
+    }
+
+    #[test]
+    fn malformed_rpn() {
+        let token = |kind: Rpn, pos: usize| RpnToken { kind, pos };
+        let vars = HashMap::new();
+        let kind = |rpn: &[RpnToken]| evaluate_rpn(rpn, &vars).map_err(|e| (e.kind, e.pos));
+
+        assert_eq!(kind(&[]), Err((ErrorKind::MalformedRpn, 0)));
+        assert_eq!(
+            kind(&[token(Rpn::Number(1.0), 0), token(Rpn::Binary('+'), 2)]),
+            Err((ErrorKind::MalformedRpn, 2))
+        );
+        assert_eq!(
+            kind(&[token(Rpn::Neg, 0)]),
+            Err((ErrorKind::MalformedRpn, 0))
+        );
+        assert_eq!(
+            kind(&[token(Rpn::Number(1.0), 0), token(Rpn::Number(2.0), 2)]),
+            Err((ErrorKind::MalformedRpn, 2))
+        );
+        assert_eq!(
+            kind(&[
+                token(Rpn::Number(1.0), 0),
+                token(Rpn::Number(2.0), 2),
+                token(Rpn::Binary('&'), 4)
+            ]),
+            Err((ErrorKind::MalformedRpn, 4))
+        );
+        assert_eq!(
+            kind(&[
+                token(Rpn::Number(1.0), 4),
+                token(Rpn::Call("nope".to_string(), 1), 0)
+            ]),
+            Err((ErrorKind::UnknownFunction("nope".to_string()), 0))
+        );
+        assert_eq!(
+            kind(&[
+                token(Rpn::Number(1.0), 4),
+                token(Rpn::Call("pow".to_string(), 1), 0)
+            ]),
+            Err((
+                ErrorKind::WrongArgumentCount {
+                    name: "pow".to_string(),
+                    found: 1
+                },
+                0
+            ))
+        );
+        assert_eq!(
+            kind(&[token(Rpn::Call("max".to_string(), 2), 0)]),
+            Err((ErrorKind::MalformedRpn, 0))
+        );
+        assert_eq!(
+            kind(&[
+                token(Rpn::Number(1.0), 4),
+                token(Rpn::Call("max".to_string(), usize::MAX), 0)
+            ]),
+            Err((ErrorKind::MalformedRpn, 0))
+        );
+        assert_eq!(
+            kind(&[
+                token(Rpn::Number(9.0), 5),
+                token(Rpn::Call("sqrt".to_string(), 1), 0)
+            ]),
+            Ok(3.0)
+        );
+    }
+
+    #[test]
+    fn error_message() {
+        assert_eq!(
+            eval("2 * y").unwrap_err().to_string(),
+            "unknown variable 'y' at position 4"
+        );
+    }
+}
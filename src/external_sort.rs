@@ -0,0 +1,519 @@
+use crate::heap_sort::sift_down;
+use crate::quick_sort::introsort_by;
+use crate::tim_sort::tim_sort_by;
+use std::cmp::Ordering;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+// Default number of runs merged at once, also how many run files are open at a time
+const DEFAULT_FAN_IN: usize = 64;
+
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+// How records are read from and written to the input, the output and the run files
+pub trait RecordCodec {
+    type Record;
+
+    // None at a clean end of input
+    fn read<R: BufRead>(&self, reader: &mut R) -> io::Result<Option<Self::Record>>;
+    fn write<W: Write>(&self, record: &Self::Record, writer: &mut W) -> io::Result<()>;
+    // Heap bytes a record takes, counted against the memory budget
+    fn size(&self, record: &Self::Record) -> usize;
+}
+
+// Records of exactly `width` bytes back to back, no separators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedWidth {
+    width: usize,
+}
+
+impl FixedWidth {
+    pub fn new(width: usize) -> FixedWidth {
+        if width == 0 {
+            panic!("Records need at least one byte");
+        }
+
+        FixedWidth { width }
+    }
+}
+
+impl RecordCodec for FixedWidth {
+    type Record = Vec<u8>;
+
+    fn read<R: BufRead>(&self, reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+        let mut record = vec![0; self.width];
+        let mut filled = 0;
+
+        while filled < self.width {
+            match reader.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        match filled {
+            0 => Ok(None),
+            n if n < self.width => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Input ends in the middle of a record",
+            )),
+            _ => Ok(Some(record)),
+        }
+    }
+
+    fn write<W: Write>(&self, record: &Vec<u8>, writer: &mut W) -> io::Result<()> {
+        writer.write_all(record)
+    }
+
+    fn size(&self, record: &Vec<u8>) -> usize {
+        record.len()
+    }
+}
+
+// Newline separated records, the newline isn't part of the record. Every record is
+// written back with a newline, so a missing one at the very end gets added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Lines;
+
+impl RecordCodec for Lines {
+    type Record = Vec<u8>;
+
+    fn read<R: BufRead>(&self, reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn write<W: Write>(&self, record: &Vec<u8>, writer: &mut W) -> io::Result<()> {
+        writer.write_all(record)?;
+        writer.write_all(b"\n")
+    }
+
+    fn size(&self, record: &Vec<u8>) -> usize {
+        record.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SortStats {
+    pub records: usize,
+    pub runs: usize,   // sorted runs spilled to disk, 0 if everything fit in memory
+    pub passes: usize, // merge passes, the last one writes the output
+}
+
+pub struct ExternalSort<C: RecordCodec> {
+    pub codec: C,
+    pub memory_budget: usize, // bytes held in memory while a run is built, sorting it included
+    pub fan_in: usize,
+    pub temp_dir: PathBuf,
+    pub stable: bool, // false sorts the runs with introsort, which needs no extra memory
+}
+
+impl<C: RecordCodec> ExternalSort<C> {
+    pub fn new(codec: C, memory_budget: usize) -> ExternalSort<C> {
+        ExternalSort {
+            codec,
+            memory_budget,
+            fan_in: DEFAULT_FAN_IN,
+            temp_dir: env::temp_dir(),
+            stable: true,
+        }
+    }
+
+    pub fn sort<R: Read, W: Write>(&self, input: R, output: W) -> io::Result<SortStats>
+    where
+        C::Record: Ord + Clone,
+    {
+        self.sort_by(input, output, C::Record::cmp)
+    }
+
+    pub fn sort_by_key<R: Read, W: Write, K: Ord, F>(
+        &self,
+        input: R,
+        output: W,
+        mut key: F,
+    ) -> io::Result<SortStats>
+    where
+        C::Record: Clone,
+        F: FnMut(&C::Record) -> K,
+    {
+        self.sort_by(input, output, |a, b| key(a).cmp(&key(b)))
+    }
+
+    // Reads records until the memory budget is used up, sorts them and spills them to a
+    // run file, over and over. The runs are then merged with a heap, in several passes if
+    // there are more of them than fan_in. Stable unless turned off, since tim sort is and
+    // ties in the merge go to the earlier run. Run files are removed when they're not
+    // needed anymore, errors included.
+    pub fn sort_by<R: Read, W: Write, F>(
+        &self,
+        input: R,
+        output: W,
+        mut cmp: F,
+    ) -> io::Result<SortStats>
+    where
+        C::Record: Clone,
+        F: FnMut(&C::Record, &C::Record) -> Ordering,
+    {
+        if self.fan_in < 2 {
+            panic!("Can't merge fewer than two runs at a time");
+        }
+
+        let mut input = BufReader::new(input);
+        let mut output = BufWriter::new(output);
+        let mut stats = SortStats::default();
+
+        // Tim sort copies up to half of the records into its merge buffer, so only two
+        // thirds of the budget go to records. Introsort sorts in place and gets all of it.
+        let chunk_budget = if self.stable {
+            self.memory_budget - self.memory_budget / 3
+        } else {
+            self.memory_budget
+        };
+
+        let mut runs = Vec::new();
+        let mut chunk = Vec::new();
+        let mut used = 0;
+
+        while let Some(record) = self.codec.read(&mut input)? {
+            used += self.codec.size(&record) + mem::size_of::<C::Record>();
+            chunk.push(record);
+            stats.records += 1;
+
+            if used >= chunk_budget {
+                self.sort_chunk(&mut chunk, &mut cmp);
+                runs.push(self.spill(&chunk)?);
+                chunk.clear();
+                used = 0;
+            }
+        }
+        self.sort_chunk(&mut chunk, &mut cmp);
+
+        // It all fit, nothing has to go through the disk
+        if runs.is_empty() {
+            for record in chunk.iter() {
+                self.codec.write(record, &mut output)?;
+            }
+            output.flush()?;
+            return Ok(stats);
+        }
+
+        if !chunk.is_empty() {
+            runs.push(self.spill(&chunk)?);
+        }
+        drop(chunk);
+        stats.runs = runs.len();
+
+        while runs.len() > self.fan_in {
+            let mut merged = Vec::new();
+            for group in runs.chunks(self.fan_in) {
+                let (run, file) = RunFile::create(&self.temp_dir)?;
+                let mut writer = BufWriter::new(file);
+                self.merge(group, &mut writer, &mut cmp)?;
+                writer.flush()?;
+                merged.push(run);
+            }
+
+            runs = merged;
+            stats.passes += 1;
+        }
+
+        self.merge(&runs, &mut output, &mut cmp)?;
+        output.flush()?;
+        stats.passes += 1;
+
+        Ok(stats)
+    }
+
+    fn sort_chunk<F>(&self, chunk: &mut [C::Record], cmp: &mut F)
+    where
+        C::Record: Clone,
+        F: FnMut(&C::Record, &C::Record) -> Ordering,
+    {
+        if self.stable {
+            tim_sort_by(chunk, cmp);
+        } else {
+            introsort_by(chunk, cmp);
+        }
+    }
+
+    fn spill(&self, records: &[C::Record]) -> io::Result<RunFile> {
+        let (run, file) = RunFile::create(&self.temp_dir)?;
+        let mut writer = BufWriter::new(file);
+        for record in records.iter() {
+            self.codec.write(record, &mut writer)?;
+        }
+        writer.flush()?;
+
+        Ok(run)
+    }
+
+    // k-way merge with a min heap holding the next record of every run
+    fn merge<W: Write, F>(&self, runs: &[RunFile], output: &mut W, cmp: &mut F) -> io::Result<()>
+    where
+        F: FnMut(&C::Record, &C::Record) -> Ordering,
+    {
+        let mut readers = Vec::with_capacity(runs.len());
+        for run in runs.iter() {
+            readers.push(BufReader::new(File::open(&run.path)?));
+        }
+
+        let mut heap = Vec::with_capacity(runs.len());
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = self.codec.read(reader)? {
+                heap.push(Head { record, run });
+            }
+        }
+
+        // sift_down builds a max heap, so reversed to get the smallest on top
+        let mut order = |a: &Head<C::Record>, b: &Head<C::Record>| {
+            cmp(&a.record, &b.record).then(a.run.cmp(&b.run)).reverse()
+        };
+        let end = heap.len();
+        for root in (0..end / 2).rev() {
+            sift_down(&mut heap, root, end, &mut order);
+        }
+
+        while !heap.is_empty() {
+            self.codec.write(&heap[0].record, output)?;
+
+            match self.codec.read(&mut readers[heap[0].run])? {
+                Some(record) => heap[0].record = record,
+                None => {
+                    heap.swap_remove(0);
+                }
+            }
+
+            let end = heap.len();
+            sift_down(&mut heap, 0, end, &mut order);
+        }
+
+        Ok(())
+    }
+}
+
+struct Head<T> {
+    record: T,
+    run: usize,
+}
+
+// Removes the file when dropped
+struct RunFile {
+    path: PathBuf,
+}
+
+impl RunFile {
+    // Never opens a file that's already there, so whatever has that name (a leftover from
+    // an earlier process with the same id, a symlink planted in a shared temp dir) is
+    // neither truncated nor removed afterwards
+    fn create(dir: &Path) -> io::Result<(RunFile, File)> {
+        let id = NEXT_RUN.fetch_add(1, AtomicOrdering::Relaxed);
+        let path = dir.join(format!("algos-run-{}-{}", process::id(), id));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok((RunFile { path }, file))
+    }
+}
+
+impl Drop for RunFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    // A fresh directory per test, so checking that the runs are gone is easy
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("algos-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn is_empty(dir: &Path) -> bool {
+        fs::read_dir(dir).unwrap().next().is_none()
+    }
+
+    #[test]
+    fn test() {
+        let sorter = ExternalSort::new(Lines, 1 << 20);
+        let mut output = Vec::new();
+        let stats = sorter.sort(&b"pear\napple\nfig"[..], &mut output).unwrap();
+
+        assert_eq!(output, b"apple\nfig\npear\n");
+        assert_eq!(stats.records, 3);
+        assert_eq!(stats.runs, 0);
+    }
+
+    #[test]
+    fn fixed_width_file() {
+        let dir = temp_dir("fixed-width");
+        let input_path = dir.join("input");
+        let output_path = dir.join("output");
+
+        let mut rng = XorShift::new(38);
+        let mut records: Vec<Vec<u8>> = (0..10_000)
+            .map(|_| (0..16).map(|_| rng.range(0, 256) as u8).collect())
+            .collect();
+        fs::write(&input_path, records.concat()).unwrap();
+
+        let mut sorter = ExternalSort::new(FixedWidth::new(16), 16 * 1024);
+        sorter.temp_dir = dir.join("runs");
+        fs::create_dir(&sorter.temp_dir).unwrap();
+
+        let stats = sorter
+            .sort(
+                File::open(&input_path).unwrap(),
+                File::create(&output_path).unwrap(),
+            )
+            .unwrap();
+
+        records.sort();
+        assert_eq!(fs::read(&output_path).unwrap(), records.concat());
+        assert_eq!(stats.records, 10_000);
+        assert!(stats.runs > 1);
+        assert_eq!(stats.passes, 1);
+        assert!(is_empty(&sorter.temp_dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unstable_runs() {
+        let dir = temp_dir("unstable");
+        let mut rng = XorShift::new(38);
+        let mut records: Vec<Vec<u8>> = (0..3000)
+            .map(|_| (0..8).map(|_| rng.range(0, 256) as u8).collect())
+            .collect();
+        let input = records.concat();
+
+        let mut sorter = ExternalSort::new(FixedWidth::new(8), 6 * 1024);
+        sorter.temp_dir = dir.clone();
+        let stable = sorter.sort(&input[..], io::sink()).unwrap();
+
+        sorter.stable = false;
+        let mut output = Vec::new();
+        let unstable = sorter.sort(&input[..], &mut output).unwrap();
+
+        records.sort();
+        assert_eq!(output, records.concat());
+        // Without the merge buffer the same budget holds half as many records again
+        assert_eq!(stable.runs, 24);
+        assert_eq!(unstable.runs, 16);
+        assert!(is_empty(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lines_several_passes_stable() {
+        let dir = temp_dir("lines");
+        let input_path = dir.join("input");
+        let output_path = dir.join("output");
+
+        // "key original-position", sorted by the key only
+        let mut rng = XorShift::new(39);
+        let lines: Vec<String> = (0..5000)
+            .map(|i| format!("{} {}", rng.range(0, 50), i))
+            .collect();
+        fs::write(&input_path, lines.join("\n")).unwrap();
+
+        let mut sorter = ExternalSort::new(Lines, 2000);
+        sorter.fan_in = 3;
+        sorter.temp_dir = dir.join("runs");
+        fs::create_dir(&sorter.temp_dir).unwrap();
+
+        let key = |line: &Vec<u8>| -> u32 {
+            let end = line.iter().position(|&b| b == b' ').unwrap();
+            std::str::from_utf8(&line[..end]).unwrap().parse().unwrap()
+        };
+        let stats = sorter
+            .sort_by_key(
+                File::open(&input_path).unwrap(),
+                File::create(&output_path).unwrap(),
+                key,
+            )
+            .unwrap();
+
+        let mut expected = lines.clone();
+        expected.sort_by_key(|line| key(&line.as_bytes().to_vec()));
+        let output = fs::read_to_string(&output_path).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+
+        assert!(stats.runs > 9);
+        assert!(stats.passes > 2);
+        assert!(is_empty(&sorter.temp_dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_input() {
+        let dir = temp_dir("truncated");
+        let mut sorter = ExternalSort::new(FixedWidth::new(4), 8);
+        sorter.temp_dir = dir.clone();
+
+        let input: Vec<u8> = (0..42).collect();
+        let err = sorter.sort(&input[..], io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // The runs spilled before the error are gone too
+        assert!(is_empty(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn existing_run_files_are_left_alone() {
+        let dir = temp_dir("existing");
+        // Whatever is already sitting where the next runs would go. Other tests take ids
+        // too, so a good stretch of them is covered.
+        let next = NEXT_RUN.load(AtomicOrdering::Relaxed);
+        let planted: Vec<PathBuf> = (next..next + 10_000)
+            .map(|id| dir.join(format!("algos-run-{}-{}", process::id(), id)))
+            .collect();
+        for path in planted.iter() {
+            fs::write(path, b"keep").unwrap();
+        }
+
+        let mut sorter = ExternalSort::new(FixedWidth::new(4), 8);
+        sorter.temp_dir = dir.clone();
+        let input: Vec<u8> = (0..64).collect();
+        let err = sorter.sort(&input[..], io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        for path in planted.iter() {
+            assert_eq!(fs::read(path).unwrap(), b"keep");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_input() {
+        let sorter = ExternalSort::new(FixedWidth::new(8), 0);
+        let mut output = Vec::new();
+        let stats = sorter.sort(io::empty(), &mut output).unwrap();
+        assert!(output.is_empty());
+        assert_eq!(stats, SortStats::default());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use std::cmp::Ordering;
+
+pub fn heap_sort<T: Ord>(arr: &mut [T]) {
+    heap_sort_by(arr, T::cmp);
+}
+
+pub fn heap_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    heap_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn heap_sort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    heap_sort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
+
+pub fn heap_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    // Building a max heap bottom up, starting from the last parent
+    let len = arr.len();
+    for root in (0..len / 2).rev() {
+        sift_down_tracked(&mut arr, root, len, &mut cmp);
+    }
+
+    // Moving the max to the back one by one, the heap shrinks as the sorted tail grows
+    for end in (1..len).rev() {
+        arr.swap(0, end);
+        sift_down_tracked(&mut arr, 0, end, &mut cmp);
+    }
+}
+
+pub fn sift_down<T, F>(arr: &mut [T], root: usize, end: usize, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sift_down_tracked(&mut Tracked::new(arr, &NoObserver), root, end, cmp);
+}
+
+// Pushes arr[root] down until both children are smaller, looking only at arr[..end]
+pub fn sift_down_tracked<T, F, O>(arr: &mut Tracked<T, O>, mut root: usize, end: usize, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    loop {
+        let mut child = 2 * root + 1;
+        if child >= end {
+            return;
+        }
+
+        if child + 1 < end && arr.compare(child, child + 1, cmp) == Ordering::Less {
+            child += 1;
+        }
+
+        if arr.compare(root, child, cmp) != Ordering::Less {
+            return;
+        }
+
+        arr.swap(root, child);
+        root = child;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        heap_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = ["ccc", "a", "bb"];
+        heap_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc"]);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(34);
+        for n in 0..200 {
+            let mut arr: Vec<u64> = (0..n).map(|_| rng.next_u64() % 50).collect();
+            let mut expected = arr.clone();
+            expected.sort();
+            heap_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
+}
@@ -0,0 +1,549 @@
+use crate::stack::Stack;
+
+pub trait Command {
+    type Target;
+
+    fn apply(&self, target: &mut Self::Target);
+    fn revert(&self, target: &mut Self::Target);
+}
+
+// Every state the target goes through gets a sequence number. They only ever grow,
+// which is what lets `restore` tell whether a checkpoint is behind or ahead of us.
+struct Entry<C> {
+    commands: Vec<C>,
+    seq: u64,  // state after applying the commands
+    prev: u64, // state before
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(u64);
+
+// Entries are moved between the two stacks, never copied. Only the top `undoable` entries
+// of the undo stack can be undone, the ones below fell off the bottom of the history and are
+// dropped in one go once there are max_depth of them.
+pub struct History<C: Command> {
+    undo: Stack<Entry<C>>,
+    redo: Stack<Entry<C>>,
+    max_depth: usize,
+    undoable: usize,
+    depth: usize, // entries between the very first state and the current one, evicted included
+    transaction: Option<Vec<C>>,
+    transaction_depth: usize,
+    current: u64,
+    next_seq: u64,
+    checkpoints: Vec<(u64, usize)>, // state and its depth
+}
+
+impl<C: Command> History<C> {
+    pub fn new(max_depth: usize) -> Self {
+        if max_depth == 0 {
+            panic!("History needs room for at least one entry");
+        }
+
+        History {
+            undo: Stack::new(),
+            redo: Stack::new(),
+            max_depth,
+            undoable: 0,
+            depth: 0,
+            transaction: None,
+            transaction_depth: 0,
+            current: 0,
+            next_seq: 1,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn unbounded() -> Self {
+        History::new(usize::MAX)
+    }
+
+    pub fn apply(&mut self, command: C, target: &mut C::Target) {
+        command.apply(target);
+
+        match self.transaction.as_mut() {
+            Some(commands) => commands.push(command),
+            None => self.push_entry(vec![command]),
+        }
+    }
+
+    pub fn undo(&mut self, target: &mut C::Target) -> bool {
+        self.assert_no_transaction();
+
+        if self.undoable == 0 {
+            return false;
+        }
+
+        let entry = self.undo.pop().unwrap();
+        for command in entry.commands.iter().rev() {
+            command.revert(target);
+        }
+        self.current = entry.prev;
+        self.undoable -= 1;
+        self.depth -= 1;
+        self.redo.push(entry);
+        true
+    }
+
+    pub fn redo(&mut self, target: &mut C::Target) -> bool {
+        self.assert_no_transaction();
+
+        match self.redo.pop() {
+            Some(entry) => {
+                for command in entry.commands.iter() {
+                    command.apply(target);
+                }
+                self.current = entry.seq;
+                self.undoable += 1;
+                self.depth += 1;
+                self.undo.push(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Everything applied until the matching commit is undone and redone as one entry.
+    // Transactions can nest, only the outermost commit creates the entry.
+    pub fn begin(&mut self) {
+        self.transaction_depth += 1;
+        if self.transaction.is_none() {
+            self.transaction = Some(Vec::new());
+        }
+    }
+
+    pub fn commit(&mut self) {
+        if self.transaction_depth == 0 {
+            panic!("No transaction to commit");
+        }
+
+        self.transaction_depth -= 1;
+        if self.transaction_depth > 0 {
+            return;
+        }
+
+        let commands = self.transaction.take().unwrap();
+        if !commands.is_empty() {
+            self.push_entry(commands);
+        }
+    }
+
+    // Reverts everything applied since the outermost begin and closes the transaction
+    pub fn rollback(&mut self, target: &mut C::Target) {
+        let commands = match self.transaction.take() {
+            Some(commands) => commands,
+            None => panic!("No transaction to roll back"),
+        };
+
+        for command in commands.iter().rev() {
+            command.revert(target);
+        }
+        self.transaction_depth = 0;
+    }
+
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.assert_no_transaction();
+
+        if !self.checkpoints.iter().any(|&(seq, _)| seq == self.current) {
+            self.checkpoints.push((self.current, self.depth));
+        }
+
+        Checkpoint(self.current)
+    }
+
+    pub fn is_at(&self, checkpoint: Checkpoint) -> bool {
+        self.current == checkpoint.0
+    }
+
+    // Undoes or redoes until the target is back at the checkpoint. Returns false when the
+    // checkpoint can't be reached anymore, either because it fell off the bottom of the
+    // history or because it was on a redo branch that a new command replaced.
+    pub fn restore(&mut self, checkpoint: Checkpoint, target: &mut C::Target) -> bool {
+        self.assert_no_transaction();
+
+        if !self.checkpoints.iter().any(|&(seq, _)| seq == checkpoint.0) {
+            return false;
+        }
+
+        while self.current > checkpoint.0 {
+            self.undo(target);
+        }
+        while self.current < checkpoint.0 {
+            self.redo(target);
+        }
+
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.undoable > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undoable
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    fn push_entry(&mut self, commands: Vec<C>) {
+        // A new command starts a new branch, whatever was undone is gone for good
+        self.redo.clear();
+        let current = self.current;
+        self.checkpoints.retain(|&(seq, _)| seq <= current);
+
+        let entry = Entry {
+            commands,
+            seq: self.next_seq,
+            prev: self.current,
+        };
+        self.next_seq += 1;
+        self.current = entry.seq;
+        self.depth += 1;
+        self.undo.push(entry);
+
+        if self.undoable < self.max_depth {
+            self.undoable += 1;
+            return;
+        }
+
+        // The oldest undoable entry just fell off, nothing can take us back past it
+        let oldest = self.depth - self.undoable;
+        self.checkpoints.retain(|&(_, depth)| depth >= oldest);
+
+        // Dropping the dead entries walks over the live ones, so it's only done once there
+        // are as many dead as live ones. That keeps it O(1) per entry on average.
+        if self.undo.len() - self.undoable >= self.max_depth {
+            self.undo.keep_top(self.undoable);
+        }
+    }
+
+    fn assert_no_transaction(&self) {
+        if self.transaction.is_some() {
+            panic!("Transaction is still open");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{Command, History};
+    use crate::rng::XorShift;
+
+    #[derive(Clone, Debug)]
+    enum Edit {
+        Push(i64),
+        Add(usize, i64),
+        Swap(usize, usize),
+    }
+
+    impl Command for Edit {
+        type Target = Vec<i64>;
+
+        fn apply(&self, target: &mut Vec<i64>) {
+            match *self {
+                Edit::Push(value) => target.push(value),
+                Edit::Add(idx, delta) => target[idx] += delta,
+                Edit::Swap(a, b) => target.swap(a, b),
+            }
+        }
+
+        fn revert(&self, target: &mut Vec<i64>) {
+            match *self {
+                Edit::Push(_) => {
+                    target.pop();
+                }
+                Edit::Add(idx, delta) => target[idx] -= delta,
+                Edit::Swap(a, b) => target.swap(a, b),
+            }
+        }
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut doc = vec![];
+        let mut history = History::unbounded();
+        assert!(!history.undo(&mut doc));
+
+        history.apply(Edit::Push(1), &mut doc);
+        history.apply(Edit::Push(2), &mut doc);
+        history.apply(Edit::Add(0, 10), &mut doc);
+        assert_eq!(doc, [11, 2]);
+
+        assert!(history.undo(&mut doc));
+        assert_eq!(doc, [1, 2]);
+        assert!(history.undo(&mut doc));
+        assert_eq!(doc, [1]);
+        assert!(history.redo(&mut doc));
+        assert_eq!(doc, [1, 2]);
+        assert_eq!(history.redo_len(), 1);
+
+        // New command drops the redo branch
+        history.apply(Edit::Swap(0, 1), &mut doc);
+        assert_eq!(doc, [2, 1]);
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut doc));
+
+        while history.undo(&mut doc) {}
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn bounded_depth() {
+        let mut doc = vec![];
+        let mut history = History::new(2);
+        history.apply(Edit::Push(1), &mut doc);
+        history.apply(Edit::Push(2), &mut doc);
+        history.apply(Edit::Push(3), &mut doc);
+        assert_eq!(history.undo_len(), 2);
+
+        while history.undo(&mut doc) {}
+        assert_eq!(doc, [1]);
+    }
+
+    #[test]
+    fn transactions() {
+        let mut doc = vec![];
+        let mut history = History::unbounded();
+        history.apply(Edit::Push(1), &mut doc);
+
+        history.begin();
+        history.apply(Edit::Push(2), &mut doc);
+        history.begin();
+        history.apply(Edit::Push(3), &mut doc);
+        history.commit();
+        assert!(history.in_transaction());
+        history.apply(Edit::Add(0, 5), &mut doc);
+        history.commit();
+        assert_eq!(doc, [6, 2, 3]);
+        assert_eq!(history.undo_len(), 2);
+
+        history.undo(&mut doc);
+        assert_eq!(doc, [1]);
+        history.redo(&mut doc);
+        assert_eq!(doc, [6, 2, 3]);
+
+        history.begin();
+        history.apply(Edit::Swap(0, 2), &mut doc);
+        history.apply(Edit::Push(4), &mut doc);
+        history.rollback(&mut doc);
+        assert_eq!(doc, [6, 2, 3]);
+        assert!(!history.in_transaction());
+        assert_eq!(history.undo_len(), 2);
+
+        // Empty transactions don't leave anything behind
+        history.begin();
+        history.commit();
+        assert_eq!(history.undo_len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Transaction is still open")]
+    fn undo_in_transaction() {
+        let mut doc = vec![];
+        let mut history = History::unbounded();
+        history.begin();
+        history.apply(Edit::Push(1), &mut doc);
+        history.undo(&mut doc);
+    }
+
+    #[test]
+    #[should_panic(expected = "No transaction to commit")]
+    fn commit_without_begin() {
+        let mut history: History<Edit> = History::unbounded();
+        history.commit();
+    }
+
+    #[test]
+    fn checkpoints() {
+        let mut doc = vec![];
+        let mut history = History::unbounded();
+        let empty = history.checkpoint();
+
+        history.apply(Edit::Push(1), &mut doc);
+        history.apply(Edit::Push(2), &mut doc);
+        let saved = history.checkpoint();
+        history.apply(Edit::Push(3), &mut doc);
+        assert!(!history.is_at(saved));
+
+        assert!(history.restore(saved, &mut doc));
+        assert_eq!(doc, [1, 2]);
+        assert!(history.is_at(saved));
+
+        assert!(history.restore(empty, &mut doc));
+        assert!(doc.is_empty());
+
+        // Forward again through the redo stack
+        assert!(history.restore(saved, &mut doc));
+        assert_eq!(doc, [1, 2]);
+
+        // Branching off before the checkpoint loses it
+        history.undo(&mut doc);
+        history.apply(Edit::Push(7), &mut doc);
+        assert!(!history.restore(saved, &mut doc));
+        assert_eq!(doc, [1, 7]);
+        assert!(history.restore(empty, &mut doc));
+        assert!(doc.is_empty());
+    }
+
+    #[test]
+    fn evicted_checkpoint() {
+        let mut doc = vec![];
+        let mut history = History::new(2);
+        let empty = history.checkpoint();
+        history.apply(Edit::Push(1), &mut doc);
+        let one = history.checkpoint();
+        history.apply(Edit::Push(2), &mut doc);
+        history.apply(Edit::Push(3), &mut doc);
+
+        assert!(!history.restore(empty, &mut doc));
+        assert!(history.restore(one, &mut doc));
+        assert_eq!(doc, [1]);
+    }
+
+    #[test]
+    fn many_evictions() {
+        let mut doc = vec![];
+        let mut history = History::new(3);
+        let mut saved = vec![history.checkpoint()];
+        for i in 0..20 {
+            history.apply(Edit::Push(i), &mut doc);
+            saved.push(history.checkpoint());
+
+            // Undoing and redoing in between doesn't change what's reachable
+            if i % 4 == 0 {
+                history.undo(&mut doc);
+                history.redo(&mut doc);
+            }
+            assert_eq!(history.undo_len(), (i as usize + 1).min(3));
+        }
+
+        // Only the last three entries can be undone, so only the last four states are left
+        for checkpoint in saved[..17].iter() {
+            assert!(!history.restore(*checkpoint, &mut doc));
+        }
+        assert!(history.restore(saved[17], &mut doc));
+        assert_eq!(doc, (0..17).collect::<Vec<i64>>());
+        assert!(!history.undo(&mut doc));
+        assert!(history.restore(saved[20], &mut doc));
+        assert_eq!(doc.len(), 20);
+    }
+
+    fn random_edit(rng: &mut XorShift, doc: &[i64]) -> Edit {
+        if doc.len() < 2 || rng.range(0, 3) == 0 {
+            return Edit::Push(rng.range(0, 100) as i64);
+        }
+
+        if rng.range(0, 2) == 0 {
+            Edit::Add(rng.range(0, doc.len()), rng.range(0, 10) as i64 - 5)
+        } else {
+            Edit::Swap(rng.range(0, doc.len()), rng.range(0, doc.len()))
+        }
+    }
+
+    #[test]
+    fn random_sessions_against_snapshots() {
+        let mut rng = XorShift::new(30);
+
+        for _ in 0..200 {
+            let initial: Vec<i64> = (0..rng.range(0, 5)).map(|x| x as i64).collect();
+            let mut doc = initial.clone();
+            let mut history = History::unbounded();
+            // Snapshots of the document before each undoable entry / after each redoable one
+            let mut undo_snapshots: Vec<Vec<i64>> = Vec::new();
+            let mut redo_snapshots: Vec<Vec<i64>> = Vec::new();
+
+            for _ in 0..rng.range(1, 60) {
+                match rng.range(0, 6) {
+                    0 => {
+                        let before = doc.clone();
+                        if history.undo(&mut doc) {
+                            assert_eq!(doc, undo_snapshots.pop().unwrap());
+                            redo_snapshots.push(before);
+                        }
+                    }
+                    1 => {
+                        let before = doc.clone();
+                        if history.redo(&mut doc) {
+                            assert_eq!(doc, redo_snapshots.pop().unwrap());
+                            undo_snapshots.push(before);
+                        }
+                    }
+                    2 => {
+                        // A transaction that's either kept or rolled back
+                        let before = doc.clone();
+                        history.begin();
+                        for _ in 0..rng.range(1, 4) {
+                            let edit = random_edit(&mut rng, &doc);
+                            history.apply(edit, &mut doc);
+                        }
+                        if rng.range(0, 2) == 0 {
+                            history.rollback(&mut doc);
+                            assert_eq!(doc, before);
+                        } else {
+                            history.commit();
+                            undo_snapshots.push(before);
+                            redo_snapshots.clear();
+                        }
+                    }
+                    _ => {
+                        let before = doc.clone();
+                        let edit = random_edit(&mut rng, &doc);
+                        history.apply(edit, &mut doc);
+                        undo_snapshots.push(before);
+                        redo_snapshots.clear();
+                    }
+                }
+
+                assert_eq!(history.undo_len(), undo_snapshots.len());
+                assert_eq!(history.redo_len(), redo_snapshots.len());
+            }
+
+            while history.undo(&mut doc) {}
+            assert_eq!(doc, initial);
+        }
+    }
+
+    // No Clone, entries are only ever moved between undo and redo
+    struct Append(String);
+
+    impl Command for Append {
+        type Target = String;
+
+        fn apply(&self, target: &mut String) {
+            target.push_str(&self.0);
+        }
+
+        fn revert(&self, target: &mut String) {
+            target.truncate(target.len() - self.0.len());
+        }
+    }
+
+    #[test]
+    fn commands_without_clone() {
+        let mut doc = String::new();
+        let mut history = History::new(1000);
+
+        for i in 0..5000 {
+            history.apply(Append(format!("{},", i % 10)), &mut doc);
+        }
+        assert_eq!(history.undo_len(), 1000);
+
+        for _ in 0..1000 {
+            assert!(history.undo(&mut doc));
+        }
+        assert!(!history.undo(&mut doc));
+        assert_eq!(doc.len(), 4000 * 2);
+        assert!(history.redo(&mut doc));
+        assert_eq!(history.redo_len(), 999);
+    }
+}
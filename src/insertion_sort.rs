@@ -0,0 +1,61 @@
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use std::cmp::Ordering;
+
+pub fn insertion_sort<T: Ord>(arr: &mut [T]) {
+    insertion_sort_by(arr, T::cmp);
+}
+
+pub fn insertion_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    insertion_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn insertion_sort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    insertion_sort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
+
+pub fn insertion_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    for i in 1..arr.len() {
+        // Walking the new element back until it meets something not greater (stable)
+        let mut j = i;
+        while j > 0 && arr.compare(j - 1, j, &mut cmp) == Ordering::Greater {
+            arr.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        insertion_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = [(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd')];
+        insertion_sort_by_key(&mut arr, |pair| pair.0);
+        assert_eq!(arr, [(1, 'b'), (1, 'd'), (2, 'a'), (2, 'c')]);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(33);
+        for n in 0..64 {
+            let mut arr: Vec<u64> = (0..n).map(|_| rng.next_u64() % 20).collect();
+            let mut expected = arr.clone();
+            expected.sort();
+            insertion_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
+}
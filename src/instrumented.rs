@@ -0,0 +1,684 @@
+use crate::bubble_sort::{bubble_sort_tracked, cocktail_shaker_sort_tracked};
+use crate::counting_sort::counting_sort_tracked;
+use crate::heap_sort::heap_sort_tracked;
+use crate::insertion_sort::insertion_sort_tracked;
+use crate::merge_sort::{merge_sort_tracked, MergeStrategy};
+use crate::quick_sort::{introsort_tracked, quick_sort_tracked, PivotStrategy};
+use crate::radix_sort::{radix_sort_tracked, RadixKey};
+use crate::tim_sort::tim_sort_tracked;
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::mem;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+
+// Gets told about everything a sort does to the array. Indices are always relative to
+// the whole array, even when the sort is working on a piece of it. Methods take &self
+// so the two halves of a split can both report to the same observer.
+//
+// Sorts that need a buffer get scratch space the size of the array right after it:
+// slot k of the buffer used by a piece starting at i is len + i + k. Pieces that don't
+// overlap never share scratch slots, so they can be sorted on different threads.
+pub trait Observer {
+    fn compare(&self, i: usize, j: usize);
+    fn swap(&self, i: usize, j: usize);
+    // i now holds a copy of what's at from
+    fn write(&self, i: usize, from: usize);
+}
+
+// What the plain sorts use, compiles down to nothing
+pub struct NoObserver;
+
+impl Observer for NoObserver {
+    fn compare(&self, _i: usize, _j: usize) {}
+    fn swap(&self, _i: usize, _j: usize) {}
+    fn write(&self, _i: usize, _from: usize) {}
+}
+
+// A slice that reports everything done to it. The sorts are written against this, so
+// they can run with any observer.
+pub struct Tracked<'a, T, O: Observer> {
+    arr: &'a mut [T],
+    offset: usize, // where arr starts in the whole array
+    total: usize,  // length of the whole array, scratch space starts there
+    observer: &'a O,
+}
+
+impl<'a, T, O: Observer> Tracked<'a, T, O> {
+    pub fn new(arr: &'a mut [T], observer: &'a O) -> Tracked<'a, T, O> {
+        Tracked {
+            total: arr.len(),
+            arr,
+            offset: 0,
+            observer,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arr.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arr.is_empty()
+    }
+
+    // For the sorts that go by keys, looking at an element isn't reported
+    pub fn get(&self, i: usize) -> &T {
+        &self.arr[i]
+    }
+
+    pub fn compare<F>(&self, i: usize, j: usize, cmp: &mut F) -> Ordering
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.observer.compare(self.offset + i, self.offset + j);
+        cmp(&self.arr[i], &self.arr[j])
+    }
+
+    pub fn swap(&mut self, i: usize, j: usize) {
+        self.observer.swap(self.offset + i, self.offset + j);
+        self.arr.swap(i, j);
+    }
+
+    // Moves arr[hi - 1] to lo and everything in between up by one. Reported as the
+    // swaps that walk it down, but done in one go.
+    pub fn rotate_right(&mut self, lo: usize, hi: usize) {
+        for i in (lo + 1..hi).rev() {
+            self.observer.swap(self.offset + i - 1, self.offset + i);
+        }
+        self.arr[lo..hi].rotate_right(1);
+    }
+
+    // Same as compare and swap, with j in another piece or in a buffer
+    pub fn compare_with<F>(
+        &self,
+        i: usize,
+        other: &Tracked<T, O>,
+        j: usize,
+        cmp: &mut F,
+    ) -> Ordering
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        self.observer.compare(self.offset + i, other.offset + j);
+        cmp(&self.arr[i], &other.arr[j])
+    }
+
+    pub fn swap_with(&mut self, i: usize, other: &mut Tracked<T, O>, j: usize) {
+        self.observer.swap(self.offset + i, other.offset + j);
+        mem::swap(&mut self.arr[i], &mut other.arr[j]);
+    }
+
+    // Copies arr[range] into the buffer and hands it back as this piece's scratch space
+    pub fn copy_out<'b>(&self, range: Range<usize>, buffer: &'b mut Vec<T>) -> Tracked<'b, T, O>
+    where
+        'a: 'b,
+        T: Clone,
+    {
+        let scratch = self.total + self.offset;
+        for (k, i) in range.clone().enumerate() {
+            self.observer.write(scratch + k, self.offset + i);
+        }
+
+        buffer.clear();
+        buffer.extend_from_slice(&self.arr[range]);
+        Tracked {
+            arr: buffer,
+            offset: scratch,
+            total: self.total,
+            observer: self.observer,
+        }
+    }
+
+    // A shorter lived copy, to hand to something that takes a Tracked by value
+    pub fn reborrow(&mut self) -> Tracked<'_, T, O> {
+        Tracked {
+            arr: self.arr,
+            offset: self.offset,
+            total: self.total,
+            observer: self.observer,
+        }
+    }
+
+    pub fn slice(&mut self, range: Range<usize>) -> Tracked<'_, T, O> {
+        Tracked {
+            offset: self.offset + range.start,
+            arr: &mut self.arr[range],
+            total: self.total,
+            observer: self.observer,
+        }
+    }
+
+    pub fn split_at(self, mid: usize) -> (Tracked<'a, T, O>, Tracked<'a, T, O>) {
+        let (left, right) = self.arr.split_at_mut(mid);
+        (
+            Tracked {
+                arr: left,
+                offset: self.offset,
+                total: self.total,
+                observer: self.observer,
+            },
+            Tracked {
+                arr: right,
+                offset: self.offset + mid,
+                total: self.total,
+                observer: self.observer,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Counts {
+    pub comparisons: usize,
+    pub swaps: usize,
+    pub writes: usize, // elements written, two per swap plus one per copy
+}
+
+// Atomic so the parallel sorts can share one
+#[derive(Default)]
+pub struct Counter {
+    comparisons: AtomicUsize,
+    swaps: AtomicUsize,
+    writes: AtomicUsize,
+}
+
+impl Counter {
+    pub fn new() -> Counter {
+        Counter::default()
+    }
+
+    pub fn counts(&self) -> Counts {
+        Counts {
+            comparisons: self.comparisons.load(AtomicOrdering::Relaxed),
+            swaps: self.swaps.load(AtomicOrdering::Relaxed),
+            writes: self.writes.load(AtomicOrdering::Relaxed),
+        }
+    }
+}
+
+impl Observer for Counter {
+    fn compare(&self, _i: usize, _j: usize) {
+        self.comparisons.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+
+    fn swap(&self, _i: usize, _j: usize) {
+        self.swaps.fetch_add(1, AtomicOrdering::Relaxed);
+        self.writes.fetch_add(2, AtomicOrdering::Relaxed);
+    }
+
+    fn write(&self, _i: usize, _from: usize) {
+        self.writes.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Compare(usize, usize),
+    Swap(usize, usize),
+    Write(usize, usize), // (to, from)
+}
+
+// Remembers every step, so it's O(comparisons) memory. Fine for teaching sized arrays.
+// Steps from different threads end up interleaved, but they never touch the same slots.
+#[derive(Default)]
+pub struct Recorder {
+    steps: Mutex<Vec<Step>>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    pub fn into_trace(self) -> Trace {
+        Trace {
+            steps: self.steps.into_inner().unwrap(),
+        }
+    }
+}
+
+impl Observer for Recorder {
+    fn compare(&self, i: usize, j: usize) {
+        self.steps.lock().unwrap().push(Step::Compare(i, j));
+    }
+
+    fn swap(&self, i: usize, j: usize) {
+        self.steps.lock().unwrap().push(Step::Swap(i, j));
+    }
+
+    fn write(&self, i: usize, from: usize) {
+        self.steps.lock().unwrap().push(Step::Write(i, from));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    pub fn counts(&self) -> Counts {
+        let mut counts = Counts::default();
+        for step in self.steps.iter() {
+            match step {
+                Step::Compare(..) => counts.comparisons += 1,
+                Step::Swap(..) => {
+                    counts.swaps += 1;
+                    counts.writes += 2;
+                }
+                Step::Write(..) => counts.writes += 1,
+            }
+        }
+        counts
+    }
+
+    // Runs the steps again on a copy of the input the trace was recorded on, calling
+    // visit after each one with the array as it is at that point. Ends up sorted.
+    pub fn replay<T: Clone, V>(&self, arr: &mut [T], mut visit: V)
+    where
+        V: FnMut(&Step, &[T]),
+    {
+        // The array followed by its scratch space, whatever's in there gets overwritten
+        // before it's read
+        let n = arr.len();
+        let mut all = arr.to_vec();
+        all.extend_from_slice(arr);
+
+        for step in self.steps.iter() {
+            match *step {
+                Step::Compare(..) => {}
+                Step::Swap(i, j) => all.swap(i, j),
+                Step::Write(i, from) => all[i] = all[from].clone(),
+            }
+            visit(step, &all[..n]);
+        }
+
+        arr.clone_from_slice(&all[..n]);
+    }
+
+    // {"comparisons":1,"swaps":1,"writes":2,"steps":[{"op":"compare","i":0,"j":1},...]}
+    pub fn to_json(&self) -> String {
+        let counts = self.counts();
+        let mut json = format!(
+            "{{\"comparisons\":{},\"swaps\":{},\"writes\":{},\"steps\":[",
+            counts.comparisons, counts.swaps, counts.writes
+        );
+
+        for (n, step) in self.steps.iter().enumerate() {
+            if n > 0 {
+                json.push(',');
+            }
+
+            let (op, i, j) = match *step {
+                Step::Compare(i, j) => ("compare", i, j),
+                Step::Swap(i, j) => ("swap", i, j),
+                Step::Write(i, from) => ("write", i, from),
+            };
+            write!(json, "{{\"op\":\"{}\",\"i\":{},\"j\":{}}}", op, i, j).unwrap();
+        }
+
+        json.push_str("]}");
+        json
+    }
+}
+
+// The comparison sorts that can run instrumented. New ones get a variant here once
+// they're written against Tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Bubble,
+    CocktailShaker,
+    Insertion,
+    Heap,
+    Quick(PivotStrategy),
+    Introsort,
+    Merge(MergeStrategy),
+    Tim,
+}
+
+pub fn sort_observed<T: Clone, F, O>(arr: &mut [T], algorithm: Algorithm, cmp: F, observer: &O)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let arr = Tracked::new(arr, observer);
+    match algorithm {
+        Algorithm::Bubble => bubble_sort_tracked(arr, cmp),
+        Algorithm::CocktailShaker => cocktail_shaker_sort_tracked(arr, cmp),
+        Algorithm::Insertion => insertion_sort_tracked(arr, cmp),
+        Algorithm::Heap => heap_sort_tracked(arr, cmp),
+        Algorithm::Quick(strategy) => quick_sort_tracked(arr, strategy, cmp),
+        Algorithm::Introsort => introsort_tracked(arr, cmp),
+        Algorithm::Merge(strategy) => merge_sort_tracked(arr, strategy, &mut Vec::new(), cmp),
+        Algorithm::Tim => tim_sort_tracked(arr, cmp),
+    }
+}
+
+pub fn count<T: Ord + Clone>(arr: &mut [T], algorithm: Algorithm) -> Counts {
+    let counter = Counter::new();
+    sort_observed(arr, algorithm, T::cmp, &counter);
+    counter.counts()
+}
+
+pub fn trace<T: Ord + Clone>(arr: &mut [T], algorithm: Algorithm) -> Trace {
+    let recorder = Recorder::new();
+    sort_observed(arr, algorithm, T::cmp, &recorder);
+    recorder.into_trace()
+}
+
+// The sorts that go by an integer key instead of comparing elements. They never
+// compare anything, all there is to see is how they move things around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    Counting,
+    Radix,
+}
+
+pub fn sort_by_key_observed<T, K, F, O>(
+    arr: &mut [T],
+    algorithm: KeyAlgorithm,
+    key: F,
+    observer: &O,
+) where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+    O: Observer,
+{
+    let arr = Tracked::new(arr, observer);
+    match algorithm {
+        KeyAlgorithm::Counting => counting_sort_tracked(arr, key),
+        KeyAlgorithm::Radix => radix_sort_tracked(arr, key),
+    }
+}
+
+pub fn count_by_key<K: RadixKey>(arr: &mut [K], algorithm: KeyAlgorithm) -> Counts {
+    let counter = Counter::new();
+    sort_by_key_observed(arr, algorithm, |x| *x, &counter);
+    counter.counts()
+}
+
+pub fn trace_by_key<K: RadixKey>(arr: &mut [K], algorithm: KeyAlgorithm) -> Trace {
+    let recorder = Recorder::new();
+    sort_by_key_observed(arr, algorithm, |x| *x, &recorder);
+    recorder.into_trace()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket_sort::bucket_sort_tracked;
+    use crate::parallel_sort::{par_merge_sort_tracked, par_quick_sort_tracked, ParallelConfig};
+    use crate::radix_sort::msd_radix_sort_tracked;
+    use crate::rng::XorShift;
+    use crate::selection::{partial_sort_tracked, select_nth_linear_tracked};
+
+    const ALGORITHMS: [Algorithm; 13] = [
+        Algorithm::Bubble,
+        Algorithm::CocktailShaker,
+        Algorithm::Insertion,
+        Algorithm::Heap,
+        Algorithm::Quick(PivotStrategy::Last),
+        Algorithm::Quick(PivotStrategy::Random),
+        Algorithm::Quick(PivotStrategy::MedianOfThree),
+        Algorithm::Quick(PivotStrategy::Ninther),
+        Algorithm::Introsort,
+        Algorithm::Merge(MergeStrategy::TopDown),
+        Algorithm::Merge(MergeStrategy::BottomUp),
+        Algorithm::Merge(MergeStrategy::Natural),
+        Algorithm::Tim,
+    ];
+
+    #[test]
+    fn test() {
+        let mut arr = [3, 1, 2];
+        let trace = trace(&mut arr, Algorithm::Bubble);
+        assert_eq!(arr, [1, 2, 3]);
+        assert_eq!(
+            trace.steps,
+            [
+                Step::Compare(0, 1),
+                Step::Swap(0, 1),
+                Step::Compare(1, 2),
+                Step::Swap(1, 2),
+                Step::Compare(0, 1),
+            ]
+        );
+        assert_eq!(
+            trace.to_json(),
+            "{\"comparisons\":3,\"swaps\":2,\"writes\":4,\"steps\":[\
+             {\"op\":\"compare\",\"i\":0,\"j\":1},{\"op\":\"swap\",\"i\":0,\"j\":1},\
+             {\"op\":\"compare\",\"i\":1,\"j\":2},{\"op\":\"swap\",\"i\":1,\"j\":2},\
+             {\"op\":\"compare\",\"i\":0,\"j\":1}]}"
+        );
+    }
+
+    #[test]
+    fn counts_match_the_comparator() {
+        let mut rng = XorShift::new(40);
+        for algorithm in ALGORITHMS {
+            let original: Vec<u32> = (0..300).map(|_| rng.range(0, 100) as u32).collect();
+
+            let mut comparisons = 0;
+            let counter = Counter::new();
+            let mut arr = original.clone();
+            sort_observed(
+                &mut arr,
+                algorithm,
+                |a: &u32, b: &u32| {
+                    comparisons += 1;
+                    a.cmp(b)
+                },
+                &counter,
+            );
+
+            let mut expected = original.clone();
+            expected.sort();
+            assert_eq!(arr, expected, "{:?}", algorithm);
+            assert_eq!(counter.counts().comparisons, comparisons, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn replay_sorts_the_input() {
+        let mut rng = XorShift::new(41);
+        for algorithm in ALGORITHMS {
+            for n in [0, 1, 2, 17, 100, 500] {
+                let original: Vec<u32> = (0..n).map(|_| rng.range(0, 50) as u32).collect();
+
+                let mut arr = original.clone();
+                let trace = trace(&mut arr, algorithm);
+
+                // The array and its scratch space
+                let mut replayed = original.clone();
+                let mut indices_ok = true;
+                trace.replay(&mut replayed, |step, arr| {
+                    let (Step::Compare(i, j) | Step::Swap(i, j) | Step::Write(i, j)) = *step;
+                    indices_ok &= i < 2 * arr.len() && j < 2 * arr.len();
+                });
+
+                assert!(indices_ok, "{:?}", algorithm);
+                assert_eq!(replayed, arr, "{:?}", algorithm);
+
+                // Random pivots are different every run
+                if algorithm == Algorithm::Quick(PivotStrategy::Random) {
+                    continue;
+                }
+
+                let mut counted = original.clone();
+                assert_eq!(
+                    count(&mut counted, algorithm),
+                    trace.counts(),
+                    "{:?}",
+                    algorithm
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn writes() {
+        let mut rng = XorShift::new(42);
+        let original: Vec<u32> = (0..500).map(|_| rng.range(0, 1000) as u32).collect();
+
+        for algorithm in ALGORITHMS {
+            let trace = trace(&mut original.clone(), algorithm);
+            let copies = trace
+                .steps
+                .iter()
+                .filter(|step| matches!(step, Step::Write(..)))
+                .count();
+
+            // Only the ones with a buffer copy anything
+            let buffered = matches!(algorithm, Algorithm::Merge(_) | Algorithm::Tim);
+            assert_eq!(copies > 0, buffered, "{:?}", algorithm);
+
+            let counts = trace.counts();
+            assert_eq!(counts.writes, 2 * counts.swaps + copies, "{:?}", algorithm);
+        }
+
+        // Merging [3, 4] and [1, 2] copies the left side out and swaps everything back
+        let mut arr = [3, 4, 1, 2];
+        let trace = trace(&mut arr, Algorithm::Merge(MergeStrategy::Natural));
+        assert_eq!(arr, [1, 2, 3, 4]);
+        assert_eq!(
+            trace.steps,
+            [
+                Step::Compare(1, 0),
+                Step::Compare(2, 1),
+                Step::Compare(3, 2),
+                Step::Compare(1, 2),
+                Step::Write(4, 0),
+                Step::Write(5, 1),
+                Step::Compare(2, 4),
+                Step::Swap(0, 2),
+                Step::Compare(3, 4),
+                Step::Swap(1, 3),
+                Step::Swap(2, 4),
+                Step::Swap(3, 5),
+                Step::Compare(1, 0),
+                Step::Compare(2, 1),
+                Step::Compare(3, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn by_key() {
+        let mut rng = XorShift::new(43);
+        for algorithm in [KeyAlgorithm::Counting, KeyAlgorithm::Radix] {
+            for n in [0, 1, 2, 17, 100, 500] {
+                let original: Vec<i32> = (0..n).map(|_| rng.range(0, 2000) as i32 - 1000).collect();
+                let mut expected = original.clone();
+                expected.sort();
+
+                let mut arr = original.clone();
+                let trace = trace_by_key(&mut arr, algorithm);
+                assert_eq!(arr, expected, "{:?}", algorithm);
+
+                let mut replayed = original.clone();
+                trace.replay(&mut replayed, |_, _| {});
+                assert_eq!(replayed, expected, "{:?}", algorithm);
+
+                let counts = count_by_key(&mut original.clone(), algorithm);
+                assert_eq!(counts, trace.counts(), "{:?}", algorithm);
+                assert_eq!(counts.comparisons, 0);
+            }
+        }
+
+        // Bucket sort takes f64 keys and MSD radix sort byte strings, so they get driven directly
+        let original: Vec<f64> = (0..300).map(|_| rng.next_f64() * 100.0).collect();
+        let recorder = Recorder::new();
+        let mut arr = original.clone();
+        bucket_sort_tracked(Tracked::new(&mut arr, &recorder), |x| *x);
+        let mut replayed = original.clone();
+        recorder.into_trace().replay(&mut replayed, |_, _| {});
+        assert_eq!(replayed, arr);
+        assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+
+        let original: Vec<String> = (0..300)
+            .map(|_| format!("{:x}", rng.next_u64() % 5000))
+            .collect();
+        let recorder = Recorder::new();
+        let mut arr = original.clone();
+        msd_radix_sort_tracked(Tracked::new(&mut arr, &recorder), |s| s.as_bytes());
+        let mut replayed = original.clone();
+        recorder.into_trace().replay(&mut replayed, |_, _| {});
+        assert_eq!(replayed, arr);
+        assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn selection() {
+        let mut rng = XorShift::new(44);
+        let original: Vec<u32> = (0..1000).map(|_| rng.range(0, 500) as u32).collect();
+        let mut expected = original.clone();
+        expected.sort();
+
+        let recorder = Recorder::new();
+        let mut arr = original.clone();
+        select_nth_linear_tracked(Tracked::new(&mut arr, &recorder), 500, u32::cmp);
+        assert_eq!(arr[500], expected[500]);
+        let mut replayed = original.clone();
+        recorder.into_trace().replay(&mut replayed, |_, _| {});
+        assert_eq!(replayed, arr);
+
+        let recorder = Recorder::new();
+        let mut arr = original.clone();
+        partial_sort_tracked(Tracked::new(&mut arr, &recorder), 100, u32::cmp);
+        assert_eq!(arr[..100], expected[..100]);
+        let mut replayed = original.clone();
+        recorder.into_trace().replay(&mut replayed, |_, _| {});
+        assert_eq!(replayed, arr);
+    }
+
+    #[test]
+    fn parallel() {
+        let mut rng = XorShift::new(45);
+        let original: Vec<u32> = (0..5000).map(|_| rng.range(0, 1000) as u32).collect();
+        let mut expected = original.clone();
+        expected.sort();
+        let config = ParallelConfig::new(4, 100);
+
+        for merge in [false, true] {
+            // Steps from all four threads end up in one trace, and it still replays
+            let recorder = Recorder::new();
+            let mut arr = original.clone();
+            let tracked = Tracked::new(&mut arr, &recorder);
+            if merge {
+                par_merge_sort_tracked(tracked, config, u32::cmp);
+            } else {
+                par_quick_sort_tracked(tracked, config, u32::cmp);
+            }
+            assert_eq!(arr, expected);
+
+            let trace = recorder.into_trace();
+            let mut replayed = original.clone();
+            trace.replay(&mut replayed, |_, _| {});
+            assert_eq!(replayed, expected);
+
+            let counter = Counter::new();
+            let mut arr = original.clone();
+            let tracked = Tracked::new(&mut arr, &counter);
+            if merge {
+                par_merge_sort_tracked(tracked, config, u32::cmp);
+            } else {
+                par_quick_sort_tracked(tracked, config, u32::cmp);
+            }
+            assert_eq!(counter.counts(), trace.counts());
+        }
+    }
+
+    #[test]
+    fn bubble_vs_quick() {
+        let mut arr: Vec<u32> = (0..1000).rev().collect();
+        let bubble = count(&mut arr.clone(), Algorithm::Bubble);
+        let quick = count(&mut arr, Algorithm::Introsort);
+
+        // Reversed input is bubble sort's worst case, every pair gets swapped
+        assert_eq!(bubble.swaps, 1000 * 999 / 2);
+        assert_eq!(bubble.writes, 2 * bubble.swaps);
+        assert!(quick.comparisons * 10 < bubble.comparisons);
+    }
+}
@@ -0,0 +1,34 @@
+pub mod aho_corasick;
+pub mod array_list;
+pub mod atomic_stack;
+pub mod avl_tree;
+pub mod binary_search_list;
+pub mod bst;
+pub mod bubble_sort;
+pub mod bucket_sort;
+pub mod counting_sort;
+pub mod doubly_linked_list;
+pub mod edit_distance;
+pub mod expression;
+pub mod external_sort;
+pub mod heap_sort;
+pub mod history;
+pub mod insertion_sort;
+pub mod instrumented;
+pub mod linear_search;
+pub mod merge_sort;
+pub mod min_max_stack;
+pub mod ordered_map;
+pub mod parallel_sort;
+pub mod queue;
+pub mod quick_sort;
+pub mod radix_sort;
+pub mod red_black_tree;
+pub mod rng;
+pub mod search;
+pub mod selection;
+pub mod stack;
+pub mod string_search;
+pub mod tim_sort;
+pub mod two_crystal_balls;
+pub mod vec_stack;
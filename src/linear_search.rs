@@ -1,9 +1,271 @@
-pub fn linear_search(haystack: [String; 10], needle: &String) -> bool {
-    for i in 0..haystack.len() {
-        if haystack[i] == *needle {
-            return true;
+use std::borrow::Borrow;
+use std::mem;
+
+// Elements compared at once by chunked_search, without branching on each of them
+const CHUNK: usize = 16;
+
+// Position of the first item equal to the needle. Works with anything iterable, owned
+// items or references: a [String; 10], a &[String], a Vec<&str>, a linked list...
+pub fn linear_search<I, T>(haystack: I, needle: &T) -> Option<usize>
+where
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+    T: PartialEq + ?Sized,
+{
+    find_by(haystack, |item| item.borrow() == needle)
+}
+
+pub fn find_by<I, P>(haystack: I, mut pred: P) -> Option<usize>
+where
+    I: IntoIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    for (i, item) in haystack.into_iter().enumerate() {
+        if pred(&item) {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+pub fn find_all<I, T>(haystack: I, needle: &T) -> Vec<usize>
+where
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+    T: PartialEq + ?Sized,
+{
+    find_all_by(haystack, |item| item.borrow() == needle)
+}
+
+pub fn find_all_by<I, P>(haystack: I, mut pred: P) -> Vec<usize>
+where
+    I: IntoIterator,
+    P: FnMut(&I::Item) -> bool,
+{
+    haystack
+        .into_iter()
+        .enumerate()
+        .filter(|(_, item)| pred(item))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Puts the needle in the last slot for the duration of the search, so the loop stops at it
+// even when nothing else matches. The last element is put back afterwards, also when a
+// comparison panics.
+pub fn sentinel_search<T: Eq>(arr: &mut [T], needle: T) -> Option<usize> {
+    sentinel_search_with(arr, needle, |arr, last| {
+        // Eq means the sentinel equals itself, so there's no need to watch for the end
+        let mut i = 0;
+        while arr[i] != arr[last] {
+            i += 1;
+        }
+        i
+    })
+}
+
+// Same for PartialEq, which doesn't promise the sentinel equals itself (NaN doesn't),
+// so the end is still checked instead of trusted
+pub fn sentinel_search_partial<T: PartialEq>(arr: &mut [T], needle: T) -> Option<usize> {
+    sentinel_search_with(arr, needle, |arr, last| {
+        let mut i = 0;
+        while i < last && arr[i] != arr[last] {
+            i += 1;
+        }
+        i
+    })
+}
+
+// scan gets the array with the sentinel in place and returns where it stopped
+fn sentinel_search_with<T, S>(arr: &mut [T], needle: T, scan: S) -> Option<usize>
+where
+    T: PartialEq,
+    S: FnOnce(&[T], usize) -> usize,
+{
+    if arr.is_empty() {
+        return None;
+    }
+
+    let last = arr.len() - 1;
+    let original = mem::replace(&mut arr[last], needle);
+    let guard = Restore {
+        arr,
+        original: Some(original),
+    };
+
+    let i = scan(guard.arr, last);
+    if i < last {
+        return Some(i);
+    }
+
+    // Ran into the sentinel, the real last element still has to be checked
+    if guard.original.as_ref() == Some(&guard.arr[last]) {
+        Some(last)
+    } else {
+        None
+    }
+}
+
+struct Restore<'a, T> {
+    arr: &'a mut [T],
+    original: Option<T>,
+}
+
+impl<T> Drop for Restore<'_, T> {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            let last = self.arr.len() - 1;
+            self.arr[last] = original;
+        }
+    }
+}
+
+// For slices of plain numbers. Every chunk is compared as a whole without stopping at a
+// match, which the compiler can turn into SIMD, and only the chunk with a hit is searched
+// one by one.
+pub fn chunked_search<T: Copy + PartialEq>(arr: &[T], needle: T) -> Option<usize> {
+    let chunks = arr.chunks_exact(CHUNK);
+    let rest = chunks.remainder();
+
+    for (c, chunk) in chunks.enumerate() {
+        let hit = chunk.iter().fold(false, |hit, &x| hit | (x == needle));
+        if hit {
+            return linear_search(chunk, &needle).map(|i| c * CHUNK + i);
         }
     }
 
-    false
+    linear_search(rest, &needle).map(|i| arr.len() - rest.len() + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+    use std::cell::Cell;
+    use std::collections::LinkedList;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test() {
+        let haystack: [String; 10] =
+            ["a", "b", "c", "d", "e", "f", "g", "h", "b", "j"].map(String::from);
+        assert_eq!(linear_search(&haystack, &"b".to_string()), Some(1));
+        assert_eq!(linear_search(haystack.clone(), &"j".to_string()), Some(9));
+        assert_eq!(linear_search(&haystack, &"z".to_string()), None);
+        assert_eq!(find_all(&haystack, &"b".to_string()), [1, 8]);
+
+        let words = ["pear", "apple", "fig"];
+        assert_eq!(linear_search(words.iter().copied(), "fig"), Some(2));
+
+        let list: LinkedList<u32> = (0..10).collect();
+        assert_eq!(linear_search(&list, &7), Some(7));
+        assert_eq!(find_by(&list, |x| **x > 3), Some(4));
+        assert_eq!(find_all_by(0..20, |x| x % 7 == 0), [0, 7, 14]);
+    }
+
+    #[test]
+    fn sentinel() {
+        let mut arr = [5, 3, 9, 3, 7];
+        assert_eq!(sentinel_search(&mut arr, 3), Some(1));
+        assert_eq!(sentinel_search(&mut arr, 7), Some(4));
+        assert_eq!(sentinel_search(&mut arr, 8), None);
+        assert_eq!(sentinel_search(&mut [], 8), None);
+        // Looks like it was never touched
+        assert_eq!(arr, [5, 3, 9, 3, 7]);
+
+        let mut arr = vec!["x".to_string(), "y".to_string()];
+        assert_eq!(sentinel_search(&mut arr, "y".to_string()), Some(1));
+        assert_eq!(sentinel_search(&mut arr, "z".to_string()), None);
+        assert_eq!(arr, ["x", "y"]);
+    }
+
+    #[test]
+    fn sentinel_nan() {
+        // NaN isn't equal to anything, itself included, so it's never found
+        let mut arr = [1.0, 2.0];
+        assert_eq!(sentinel_search_partial(&mut arr, f64::NAN), None);
+        assert_eq!(arr, [1.0, 2.0]);
+
+        let mut arr = [f64::NAN, 2.0, f64::NAN];
+        assert_eq!(sentinel_search_partial(&mut arr, f64::NAN), None);
+        assert_eq!(sentinel_search_partial(&mut arr, 2.0), Some(1));
+        assert!(arr[2].is_nan());
+    }
+
+    // Counts how often it's compared
+    struct Counted<'a>(u32, &'a Cell<usize>);
+
+    impl PartialEq for Counted<'_> {
+        fn eq(&self, other: &Counted) -> bool {
+            self.1.set(self.1.get() + 1);
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Counted<'_> {}
+
+    #[test]
+    fn sentinel_stops_at_itself() {
+        let comparisons = Cell::new(0);
+        let mut arr: Vec<Counted> = (0..5).map(|x| Counted(x, &comparisons)).collect();
+
+        // The loop runs into the sentinel and compares it with itself, one more comparison
+        // than the checked loop which stops before the last slot. Plus one for the original.
+        assert_eq!(sentinel_search(&mut arr, Counted(9, &comparisons)), None);
+        assert_eq!(comparisons.get(), 5 + 1);
+
+        comparisons.set(0);
+        assert_eq!(
+            sentinel_search_partial(&mut arr, Counted(9, &comparisons)),
+            None
+        );
+        assert_eq!(comparisons.get(), 4 + 1);
+
+        comparisons.set(0);
+        assert_eq!(sentinel_search(&mut arr, Counted(2, &comparisons)), Some(2));
+        assert_eq!(comparisons.get(), 3);
+        assert!(arr.iter().map(|c| c.0).eq(0..5));
+    }
+
+    #[derive(Debug)]
+    struct Grumpy(u32);
+
+    impl PartialEq for Grumpy {
+        fn eq(&self, other: &Grumpy) -> bool {
+            if self.0 == 13 {
+                panic!("Unlucky");
+            }
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Grumpy {}
+
+    #[test]
+    fn sentinel_restores_on_panic() {
+        let mut arr = [Grumpy(1), Grumpy(13), Grumpy(42)];
+        let result = panic::catch_unwind(AssertUnwindSafe(|| sentinel_search(&mut arr, Grumpy(7))));
+        assert!(result.is_err());
+        assert_eq!(arr[2].0, 42);
+    }
+
+    #[test]
+    fn chunked_against_linear() {
+        let mut rng = XorShift::new(44);
+        for n in [0, 1, 15, 16, 17, 33, 100, 1000] {
+            let arr: Vec<u8> = (0..n).map(|_| rng.range(0, 64) as u8).collect();
+            for needle in 0..70 {
+                assert_eq!(chunked_search(&arr, needle), linear_search(&arr, &needle));
+                assert_eq!(
+                    sentinel_search(&mut arr.clone(), needle),
+                    linear_search(&arr, &needle)
+                );
+            }
+        }
+
+        let arr: Vec<f64> = (0..100).map(|i| i as f64 / 2.0).collect();
+        assert_eq!(chunked_search(&arr, 37.5), Some(75));
+        assert_eq!(chunked_search(&arr, 0.25), None);
+    }
 }
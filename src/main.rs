@@ -1,11 +1 @@
-mod array_list;
-mod binary_search_list;
-mod bubble_sort;
-mod doubly_linked_list;
-mod linear_search;
-mod queue;
-mod quick_sort;
-mod stack;
-mod two_crystal_balls;
-
 fn main() {}
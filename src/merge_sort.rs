@@ -0,0 +1,312 @@
+use crate::insertion_sort::insertion_sort_tracked;
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use std::cmp::Ordering;
+
+// Small pieces are sorted with insertion sort before merging starts
+const INSERTION_THRESHOLD: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    TopDown,
+    BottomUp,
+    Natural, // merges the runs already present in the input
+}
+
+pub fn merge_sort<T: Ord + Clone>(arr: &mut [T]) {
+    merge_sort_by(arr, T::cmp);
+}
+
+pub fn merge_sort_by_key<T: Clone, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    merge_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn merge_sort_by<T: Clone, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_sort_with(arr, MergeStrategy::TopDown, &mut Vec::new(), cmp);
+}
+
+// The buffer only ever holds the left half of a merge. Passing one with enough capacity
+// (arr.len() is always enough) means sorting doesn't allocate at all, its contents are
+// overwritten and left unspecified afterwards.
+pub fn merge_sort_with<T: Clone, F>(
+    arr: &mut [T],
+    strategy: MergeStrategy,
+    buffer: &mut Vec<T>,
+    cmp: F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_sort_tracked(Tracked::new(arr, &NoObserver), strategy, buffer, cmp);
+}
+
+pub fn merge_sort_tracked<T: Clone, F, O>(
+    arr: Tracked<T, O>,
+    strategy: MergeStrategy,
+    buffer: &mut Vec<T>,
+    mut cmp: F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    match strategy {
+        MergeStrategy::TopDown => top_down(arr, buffer, &mut cmp),
+        MergeStrategy::BottomUp => bottom_up(arr, buffer, &mut cmp),
+        MergeStrategy::Natural => natural(arr, buffer, &mut cmp),
+    }
+}
+
+fn top_down<T: Clone, F, O>(mut arr: Tracked<T, O>, buffer: &mut Vec<T>, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if arr.len() <= INSERTION_THRESHOLD {
+        insertion_sort_tracked(arr, &mut *cmp);
+        return;
+    }
+
+    let mid = arr.len() / 2;
+    let (left, right) = arr.reborrow().split_at(mid);
+    top_down(left, buffer, cmp);
+    top_down(right, buffer, cmp);
+    merge_tracked(&mut arr, mid, buffer, cmp);
+}
+
+fn bottom_up<T: Clone, F, O>(mut arr: Tracked<T, O>, buffer: &mut Vec<T>, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let len = arr.len();
+    for lo in (0..len).step_by(INSERTION_THRESHOLD) {
+        let hi = (lo + INSERTION_THRESHOLD).min(len);
+        insertion_sort_tracked(arr.slice(lo..hi), &mut *cmp);
+    }
+
+    let mut width = INSERTION_THRESHOLD;
+    while width < len {
+        for lo in (0..len).step_by(2 * width) {
+            let hi = (lo + 2 * width).min(len);
+            if hi - lo > width {
+                merge_tracked(&mut arr.slice(lo..hi), width, buffer, cmp);
+            }
+        }
+        width *= 2;
+    }
+}
+
+// Keeps merging neighbouring runs until there's only one left. Sorted input is a single
+// pass over the data, and nothing gets allocated to remember where the runs are.
+fn natural<T: Clone, F, O>(mut arr: Tracked<T, O>, buffer: &mut Vec<T>, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let len = arr.len();
+    loop {
+        let mut lo = 0;
+        let mut merged = false;
+
+        while lo < len {
+            let mid = lo + find_run_tracked(&mut arr.slice(lo..len), cmp);
+            if mid == len {
+                break;
+            }
+
+            let hi = mid + find_run_tracked(&mut arr.slice(mid..len), cmp);
+            merge_tracked(&mut arr.slice(lo..hi), mid - lo, buffer, cmp);
+            merged = true;
+            lo = hi;
+        }
+
+        if !merged {
+            return;
+        }
+    }
+}
+
+pub fn find_run<T, F>(arr: &mut [T], cmp: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    find_run_tracked(&mut Tracked::new(arr, &NoObserver), cmp)
+}
+
+// Length of the run at the start of arr. Strictly descending runs get reversed in place,
+// strictly so that reversing can't change the order of equal elements.
+pub fn find_run_tracked<T, F, O>(arr: &mut Tracked<T, O>, cmp: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if arr.len() < 2 {
+        return arr.len();
+    }
+
+    let mut end = 2;
+    if arr.compare(1, 0, cmp) == Ordering::Less {
+        while end < arr.len() && arr.compare(end, end - 1, cmp) == Ordering::Less {
+            end += 1;
+        }
+        for i in 0..end / 2 {
+            arr.swap(i, end - 1 - i);
+        }
+    } else {
+        while end < arr.len() && arr.compare(end, end - 1, cmp) != Ordering::Less {
+            end += 1;
+        }
+    }
+
+    end
+}
+
+pub fn merge<T: Clone, F>(arr: &mut [T], mid: usize, buffer: &mut Vec<T>, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    merge_tracked(&mut Tracked::new(arr, &NoObserver), mid, buffer, cmp);
+}
+
+// Merges the sorted arr[..mid] and arr[mid..]. Only the left side is copied out,
+// the right side is consumed in place since we never write past where we read from it.
+pub fn merge_tracked<T: Clone, F, O>(
+    arr: &mut Tracked<T, O>,
+    mid: usize,
+    buffer: &mut Vec<T>,
+    cmp: &mut F,
+) where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if mid == 0 || mid == arr.len() || arr.compare(mid - 1, mid, cmp) != Ordering::Greater {
+        return;
+    }
+
+    let mut left = arr.copy_out(0..mid, buffer);
+
+    let mut i = 0; // in left
+    let mut j = mid; // in arr
+    let mut k = 0; // where the next element goes
+
+    while i < left.len() && j < arr.len() {
+        // Taking from the left on ties keeps the sort stable
+        if arr.compare_with(j, &left, i, cmp) == Ordering::Less {
+            arr.swap(k, j);
+            j += 1;
+        } else {
+            arr.swap_with(k, &mut left, i);
+            i += 1;
+        }
+        k += 1;
+    }
+
+    // Leftovers on the right are in place already
+    while i < left.len() {
+        arr.swap_with(k, &mut left, i);
+        i += 1;
+        k += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    const STRATEGIES: [MergeStrategy; 3] = [
+        MergeStrategy::TopDown,
+        MergeStrategy::BottomUp,
+        MergeStrategy::Natural,
+    ];
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        merge_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = vec!["bb".to_string(), "a".to_string(), "ccc".to_string()];
+        merge_sort_by(&mut arr, |a, b| b.cmp(a));
+        assert_eq!(arr, ["ccc", "bb", "a"]);
+
+        merge_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc"]);
+    }
+
+    #[test]
+    fn runs() {
+        let mut arr = [1, 2, 2, 5, 3];
+        assert_eq!(find_run(&mut arr, &mut i32::cmp), 4);
+
+        let mut arr = [5, 4, 1, 1];
+        assert_eq!(find_run(&mut arr, &mut i32::cmp), 3);
+        assert_eq!(arr, [1, 4, 5, 1]);
+    }
+
+    #[test]
+    fn all_strategies_against_std() {
+        let mut rng = XorShift::new(34);
+        for strategy in STRATEGIES {
+            for n in [0, 1, 2, 15, 16, 17, 31, 32, 33, 100, 1000, 4097] {
+                for modulo in [2, 10, 1_000_000] {
+                    let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                    let mut expected = original.clone();
+                    expected.sort();
+
+                    let mut arr = original.clone();
+                    merge_sort_with(&mut arr, strategy, &mut Vec::new(), u64::cmp);
+                    assert_eq!(arr, expected, "{:?}", strategy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn natural_on_runs() {
+        let mut arr: Vec<u32> = (0..1000).chain((0..500).rev()).chain(200..700).collect();
+        let mut expected = arr.clone();
+        expected.sort();
+
+        let mut comparisons = 0;
+        merge_sort_with(&mut arr, MergeStrategy::Natural, &mut Vec::new(), |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        assert_eq!(arr, expected);
+        // Three runs are done after a few linear passes, nowhere near n log n
+        assert!(comparisons < 6 * expected.len());
+    }
+
+    #[test]
+    fn buffer_is_reused() {
+        let mut buffer: Vec<u64> = Vec::with_capacity(1000);
+        let capacity = buffer.capacity();
+        let mut rng = XorShift::new(35);
+
+        for strategy in STRATEGIES {
+            let mut arr: Vec<u64> = (0..1000).map(|_| rng.next_u64()).collect();
+            merge_sort_with(&mut arr, strategy, &mut buffer, u64::cmp);
+            assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+            assert_eq!(buffer.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn stability() {
+        let mut rng = XorShift::new(36);
+        for strategy in STRATEGIES {
+            for n in [0, 5, 16, 17, 100, 1000] {
+                // (key, original position), few keys so plenty of ties
+                let mut arr: Vec<(u32, usize)> =
+                    (0..n).map(|i| (rng.range(0, 4) as u32, i)).collect();
+                merge_sort_with(&mut arr, strategy, &mut Vec::new(), |a, b| a.0.cmp(&b.0));
+
+                for w in arr.windows(2) {
+                    assert!(w[0].0 < w[1].0 || (w[0].0 == w[1].0 && w[0].1 < w[1].1));
+                }
+            }
+        }
+    }
+}
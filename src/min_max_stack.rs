@@ -0,0 +1,247 @@
+use crate::stack::Stack;
+
+// Every entry remembers the min and max of everything below it (itself included),
+// so after a pop the previous extremes are right there on the new top
+#[derive(Clone)]
+struct Entry<T> {
+    value: T,
+    min: T,
+    max: T,
+}
+
+pub struct MinMaxStack<T: Ord + Clone> {
+    stack: Stack<Entry<T>>,
+}
+
+impl<T: Ord + Clone> MinMaxStack<T> {
+    pub fn new() -> Self {
+        MinMaxStack {
+            stack: Stack::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        let entry = match self.stack.peek() {
+            Some(top) => Entry {
+                min: if value < top.min {
+                    value.clone()
+                } else {
+                    top.min
+                },
+                max: if value > top.max {
+                    value.clone()
+                } else {
+                    top.max
+                },
+                value,
+            },
+            None => Entry {
+                min: value.clone(),
+                max: value.clone(),
+                value,
+            },
+        };
+
+        self.stack.push(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.stack.pop().map(|entry| entry.value)
+    }
+
+    pub fn peek(&self) -> Option<T> {
+        self.stack.peek().map(|entry| entry.value)
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.stack.peek().map(|entry| entry.min)
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.stack.peek().map(|entry| entry.max)
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+impl<T: Ord + Clone> Default for MinMaxStack<T> {
+    fn default() -> Self {
+        MinMaxStack::new()
+    }
+}
+
+// Classic two stack queue: we push onto `back` and pop from `front`, moving everything
+// over once `front` runs dry. Both stacks know their extremes, so the queue does too.
+pub struct MinMaxQueue<T: Ord + Clone> {
+    front: MinMaxStack<T>,
+    back: MinMaxStack<T>,
+}
+
+impl<T: Ord + Clone> MinMaxQueue<T> {
+    pub fn new() -> Self {
+        MinMaxQueue {
+            front: MinMaxStack::new(),
+            back: MinMaxStack::new(),
+        }
+    }
+
+    pub fn enqueue(&mut self, value: T) {
+        self.back.push(value);
+    }
+
+    pub fn deque(&mut self) -> Option<T> {
+        if self.front.is_empty() {
+            while let Some(value) = self.back.pop() {
+                self.front.push(value);
+            }
+        }
+
+        self.front.pop()
+    }
+
+    pub fn min(&self) -> Option<T> {
+        match (self.front.min(), self.back.min()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn max(&self) -> Option<T> {
+        match (self.front.max(), self.back.max()) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Ord + Clone> Default for MinMaxQueue<T> {
+    fn default() -> Self {
+        MinMaxQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::min_max_stack::{MinMaxQueue, MinMaxStack};
+    use crate::rng::XorShift;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn stack() {
+        let mut stack: MinMaxStack<u32> = MinMaxStack::new();
+        assert_eq!(stack.min(), None);
+        assert_eq!(stack.max(), None);
+        stack.push(5);
+        stack.push(3);
+        stack.push(8);
+        stack.push(3);
+        assert_eq!(stack.min(), Some(3));
+        assert_eq!(stack.max(), Some(8));
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.min(), Some(3));
+        assert_eq!(stack.pop(), Some(8));
+        assert_eq!(stack.max(), Some(5));
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.min(), Some(5));
+        assert_eq!(stack.peek(), Some(5));
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.min(), None);
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn stack_against_brute_force() {
+        let mut rng = XorShift::new(26);
+        let mut stack: MinMaxStack<u64> = MinMaxStack::new();
+        let mut expected: Vec<u64> = Vec::new();
+
+        for _ in 0..10_000 {
+            if rng.range(0, 3) == 0 {
+                assert_eq!(stack.pop(), expected.pop());
+            } else {
+                let value = rng.next_u64() % 100;
+                stack.push(value);
+                expected.push(value);
+            }
+
+            assert_eq!(stack.len(), expected.len());
+            assert_eq!(stack.min(), expected.iter().min().cloned());
+            assert_eq!(stack.max(), expected.iter().max().cloned());
+        }
+    }
+
+    #[test]
+    fn queue() {
+        let mut queue: MinMaxQueue<u32> = MinMaxQueue::new();
+        queue.enqueue(4);
+        queue.enqueue(1);
+        queue.enqueue(7);
+        assert_eq!(queue.min(), Some(1));
+        assert_eq!(queue.max(), Some(7));
+        assert_eq!(queue.deque(), Some(4));
+        assert_eq!(queue.deque(), Some(1));
+        assert_eq!(queue.min(), Some(7));
+        queue.enqueue(2);
+        assert_eq!(queue.min(), Some(2));
+        assert_eq!(queue.max(), Some(7));
+        assert_eq!(queue.deque(), Some(7));
+        assert_eq!(queue.deque(), Some(2));
+        assert_eq!(queue.deque(), None);
+        assert_eq!(queue.max(), None);
+    }
+
+    #[test]
+    fn sliding_window_against_brute_force() {
+        let mut rng = XorShift::new(27);
+        let prices: Vec<u64> = (0..5_000).map(|_| rng.next_u64() % 1_000).collect();
+
+        for window in [1, 2, 7, 64] {
+            let mut queue: MinMaxQueue<u64> = MinMaxQueue::new();
+            for (i, price) in prices.iter().enumerate() {
+                queue.enqueue(*price);
+                if queue.len() > window {
+                    queue.deque();
+                }
+
+                let lo = (i + 1).saturating_sub(window);
+                assert_eq!(queue.min(), prices[lo..=i].iter().min().cloned());
+                assert_eq!(queue.max(), prices[lo..=i].iter().max().cloned());
+            }
+        }
+    }
+
+    #[test]
+    fn queue_against_brute_force() {
+        let mut rng = XorShift::new(28);
+        let mut queue: MinMaxQueue<u64> = MinMaxQueue::new();
+        let mut expected: VecDeque<u64> = VecDeque::new();
+
+        for _ in 0..10_000 {
+            if rng.range(0, 3) == 0 {
+                assert_eq!(queue.deque(), expected.pop_front());
+            } else {
+                let value = rng.next_u64() % 100;
+                queue.enqueue(value);
+                expected.push_back(value);
+            }
+
+            assert_eq!(queue.len(), expected.len());
+            assert_eq!(queue.min(), expected.iter().min().cloned());
+            assert_eq!(queue.max(), expected.iter().max().cloned());
+        }
+    }
+}
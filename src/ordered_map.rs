@@ -0,0 +1,236 @@
+use crate::queue::Queue;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+// A sorted map kept as a binary search tree: BstMap, AvlMap and RbMap
+pub trait OrderedMap<K: Ord, V> {
+    fn len(&self) -> usize;
+    // Gives back the old value if the key was there
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+    fn remove(&mut self, key: &K) -> Option<V>;
+    fn get(&self, key: &K) -> Option<&V>;
+    fn min(&self) -> Option<(&K, &V)>;
+    fn max(&self) -> Option<(&K, &V)>;
+    fn floor(&self, key: &K) -> Option<(&K, &V)>;
+    fn ceiling(&self, key: &K) -> Option<(&K, &V)>;
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)>;
+    fn in_order(&self) -> Vec<(&K, &V)>;
+    fn pre_order(&self) -> Vec<(&K, &V)>;
+    fn post_order(&self) -> Vec<(&K, &V)>;
+    fn level_order(&self) -> Vec<(&K, &V)>;
+    fn height(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+// The trees only differ in how they insert and remove. Everything that just looks is the
+// same walk down from the root for all of them, written once below against this.
+pub(crate) trait BinaryNode<K, V> {
+    fn key(&self) -> &K;
+    fn value(&self) -> &V;
+    fn left(&self) -> Option<&Self>;
+    fn right(&self) -> Option<&Self>;
+}
+
+pub(crate) fn get<'a, K: Ord + 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+    key: &K,
+) -> Option<&'a V> {
+    let mut link = root;
+    while let Some(node) = link {
+        link = match key.cmp(node.key()) {
+            Ordering::Less => node.left(),
+            Ordering::Greater => node.right(),
+            Ordering::Equal => return Some(node.value()),
+        };
+    }
+
+    None
+}
+
+pub(crate) fn min<'a, K: 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+) -> Option<(&'a K, &'a V)> {
+    let mut node = root?;
+    while let Some(left) = node.left() {
+        node = left;
+    }
+    Some((node.key(), node.value()))
+}
+
+pub(crate) fn max<'a, K: 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+) -> Option<(&'a K, &'a V)> {
+    let mut node = root?;
+    while let Some(right) = node.right() {
+        node = right;
+    }
+    Some((node.key(), node.value()))
+}
+
+// Biggest key that's <= key
+pub(crate) fn floor<'a, K: Ord + 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+    key: &K,
+) -> Option<(&'a K, &'a V)> {
+    let mut found = None;
+    let mut link = root;
+
+    while let Some(node) = link {
+        link = match key.cmp(node.key()) {
+            Ordering::Less => node.left(),
+            Ordering::Equal => return Some((node.key(), node.value())),
+            Ordering::Greater => {
+                // Fits, but there might be a closer one on the right
+                found = Some((node.key(), node.value()));
+                node.right()
+            }
+        };
+    }
+
+    found
+}
+
+// Smallest key that's >= key
+pub(crate) fn ceiling<'a, K: Ord + 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+    key: &K,
+) -> Option<(&'a K, &'a V)> {
+    let mut found = None;
+    let mut link = root;
+
+    while let Some(node) = link {
+        link = match key.cmp(node.key()) {
+            Ordering::Greater => node.right(),
+            Ordering::Equal => return Some((node.key(), node.value())),
+            Ordering::Less => {
+                found = Some((node.key(), node.value()));
+                node.left()
+            }
+        };
+    }
+
+    found
+}
+
+// In order, only going into the subtrees that can have keys in the range
+pub(crate) fn range<'a, K: Ord + 'a, V: 'a, N: BinaryNode<K, V>, R: RangeBounds<K>>(
+    root: Option<&'a N>,
+    range: R,
+) -> Vec<(&'a K, &'a V)> {
+    let after_start = |key: &K| match range.start_bound() {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    };
+    let before_end = |key: &K| match range.end_bound() {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
+    };
+
+    let mut found = Vec::new();
+    let mut stack = Vec::new();
+    let mut link = root;
+
+    loop {
+        while let Some(node) = link {
+            if after_start(node.key()) {
+                stack.push(node);
+                link = node.left();
+            } else {
+                // All of the left side is too small as well
+                link = node.right();
+            }
+        }
+
+        let Some(node) = stack.pop() else {
+            break;
+        };
+        if !before_end(node.key()) {
+            break;
+        }
+        found.push((node.key(), node.value()));
+        link = node.right();
+    }
+
+    found
+}
+
+// Node, left, right: inserting the keys in this order builds the same tree again
+pub(crate) fn pre_order<'a, K: 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+) -> Vec<(&'a K, &'a V)> {
+    let mut found = Vec::new();
+    let mut stack: Vec<&N> = root.into_iter().collect();
+
+    while let Some(node) = stack.pop() {
+        found.push((node.key(), node.value()));
+        // Right first so the left comes off the stack first
+        stack.extend(node.right());
+        stack.extend(node.left());
+    }
+
+    found
+}
+
+// Left, right, node: children before their parent, like when freeing the tree
+pub(crate) fn post_order<'a, K: 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+) -> Vec<(&'a K, &'a V)> {
+    // Node, right, left turned around
+    let mut found = Vec::new();
+    let mut stack: Vec<&N> = root.into_iter().collect();
+
+    while let Some(node) = stack.pop() {
+        found.push((node.key(), node.value()));
+        stack.extend(node.left());
+        stack.extend(node.right());
+    }
+
+    found.reverse();
+    found
+}
+
+// Row by row from the root, breadth first
+pub(crate) fn level_order<'a, K: 'a, V: 'a, N: BinaryNode<K, V>>(
+    root: Option<&'a N>,
+) -> Vec<(&'a K, &'a V)> {
+    let mut found = Vec::new();
+    let mut queue = Queue::new();
+    if let Some(root) = root {
+        queue.enqueue(root);
+    }
+
+    while let Some(node) = queue.deque() {
+        found.push((node.key(), node.value()));
+        if let Some(left) = node.left() {
+            queue.enqueue(left);
+        }
+        if let Some(right) = node.right() {
+            queue.enqueue(right);
+        }
+    }
+
+    found
+}
+
+// Nodes on the longest path from the root down, 0 when empty
+pub(crate) fn height<K, V, N: BinaryNode<K, V>>(root: Option<&N>) -> usize {
+    let mut height = 0;
+    let mut stack: Vec<(&N, usize)> = root.map(|root| (root, 1)).into_iter().collect();
+
+    while let Some((node, depth)) = stack.pop() {
+        height = height.max(depth);
+        stack.extend(node.left().map(|left| (left, depth + 1)));
+        stack.extend(node.right().map(|right| (right, depth + 1)));
+    }
+
+    height
+}
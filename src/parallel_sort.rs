@@ -0,0 +1,289 @@
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use crate::merge_sort::{merge_sort_tracked, merge_tracked, MergeStrategy};
+use crate::quick_sort::{choose_pivot_tracked, introsort_tracked, pivot_tracked, PivotStrategy};
+use crate::rng::XorShift;
+use std::cmp::Ordering;
+use std::thread;
+
+// Below this many elements it's not worth handing work to another thread
+const DEFAULT_CUTOFF: usize = 1 << 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelConfig {
+    pub threads: usize,
+    pub cutoff: usize, // pieces this small are sorted on the current thread
+}
+
+impl ParallelConfig {
+    pub fn new(threads: usize, cutoff: usize) -> ParallelConfig {
+        if threads == 0 {
+            panic!("Need at least one thread");
+        }
+
+        ParallelConfig {
+            threads,
+            cutoff: cutoff.max(1),
+        }
+    }
+}
+
+impl Default for ParallelConfig {
+    fn default() -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        ParallelConfig::new(threads, DEFAULT_CUTOFF)
+    }
+}
+
+pub fn par_quick_sort<T: Ord + Send>(arr: &mut [T]) {
+    par_quick_sort_by(arr, T::cmp);
+}
+
+pub fn par_quick_sort_by_key<T: Send, K: Ord, F>(arr: &mut [T], key: F)
+where
+    F: Fn(&T) -> K + Sync,
+{
+    par_quick_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn par_quick_sort_by<T: Send, F>(arr: &mut [T], cmp: F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    par_quick_sort_with(arr, ParallelConfig::default(), cmp);
+}
+
+// Partitions on the current thread, then sorts the two sides at the same time with
+// the thread budget split between them. There's no randomness anywhere (ninther pivots,
+// introsort at the bottom), so the same config always gives the same output, whatever
+// order the threads happen to finish in.
+pub fn par_quick_sort_with<T: Send, F>(arr: &mut [T], config: ParallelConfig, cmp: F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    par_quick_sort_tracked(Tracked::new(arr, &NoObserver), config, cmp);
+}
+
+// The observer hears from all the threads at once
+pub fn par_quick_sort_tracked<T: Send, F, O>(arr: Tracked<T, O>, config: ParallelConfig, cmp: F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+    O: Observer + Sync,
+{
+    quick(arr, config.threads, config.cutoff, &cmp);
+}
+
+fn quick<T: Send, F, O>(mut arr: Tracked<T, O>, threads: usize, cutoff: usize, cmp: &F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+    O: Observer + Sync,
+{
+    if threads <= 1 || arr.len() <= cutoff {
+        introsort_tracked(arr, cmp);
+        return;
+    }
+
+    // Ninther doesn't use the rng
+    let mut rng = XorShift::new(1);
+    let pivot_idx = choose_pivot_tracked(&arr, PivotStrategy::Ninther, &mut rng, &mut &*cmp);
+    let (lt, gt) = pivot_tracked(&mut arr, pivot_idx, &mut &*cmp);
+
+    let (left, rest) = arr.split_at(lt);
+    let (_, right) = rest.split_at(gt - lt);
+
+    let half = threads / 2;
+    thread::scope(|s| {
+        s.spawn(|| quick(left, half, cutoff, cmp));
+        quick(right, threads - half, cutoff, cmp);
+    });
+}
+
+pub fn par_merge_sort<T: Ord + Clone + Send>(arr: &mut [T]) {
+    par_merge_sort_by(arr, T::cmp);
+}
+
+pub fn par_merge_sort_by_key<T: Clone + Send, K: Ord, F>(arr: &mut [T], key: F)
+where
+    F: Fn(&T) -> K + Sync,
+{
+    par_merge_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn par_merge_sort_by<T: Clone + Send, F>(arr: &mut [T], cmp: F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    par_merge_sort_with(arr, ParallelConfig::default(), cmp);
+}
+
+// Both halves are sorted at the same time and then merged on the current thread.
+// Stable like the serial one, so the output doesn't even depend on the config.
+pub fn par_merge_sort_with<T: Clone + Send, F>(arr: &mut [T], config: ParallelConfig, cmp: F)
+where
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    par_merge_sort_tracked(Tracked::new(arr, &NoObserver), config, cmp);
+}
+
+pub fn par_merge_sort_tracked<T: Clone + Send, F, O>(
+    arr: Tracked<T, O>,
+    config: ParallelConfig,
+    cmp: F,
+) where
+    F: Fn(&T, &T) -> Ordering + Sync,
+    O: Observer + Sync,
+{
+    let mut buffer = Vec::with_capacity(arr.len() / 2 + 1);
+    merge_par(arr, config.threads, config.cutoff, &mut buffer, &cmp);
+}
+
+fn merge_par<T: Clone + Send, F, O>(
+    mut arr: Tracked<T, O>,
+    threads: usize,
+    cutoff: usize,
+    buffer: &mut Vec<T>,
+    cmp: &F,
+) where
+    F: Fn(&T, &T) -> Ordering + Sync,
+    O: Observer + Sync,
+{
+    if threads <= 1 || arr.len() <= cutoff {
+        merge_sort_tracked(arr, MergeStrategy::TopDown, buffer, cmp);
+        return;
+    }
+
+    let mid = arr.len() / 2;
+    let half = threads / 2;
+    let (left, right) = arr.reborrow().split_at(mid);
+
+    thread::scope(|s| {
+        // The other thread needs a buffer of its own
+        s.spawn(|| merge_par(left, half, cutoff, &mut Vec::new(), cmp));
+        merge_par(right, threads - half, cutoff, buffer, cmp);
+    });
+
+    merge_tracked(&mut arr, mid, buffer, &mut &*cmp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quick_sort::quick_sort;
+    use std::time::Instant;
+
+    const CONFIGS: [(usize, usize); 5] = [(1, 1), (2, 1), (3, 16), (4, 100), (8, 1000)];
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        par_quick_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        par_merge_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = vec!["bb".to_string(), "a".to_string(), "ccc".to_string()];
+        par_quick_sort_by(&mut arr, |a, b| b.cmp(a));
+        assert_eq!(arr, ["ccc", "bb", "a"]);
+
+        par_merge_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least one thread")]
+    fn zero_threads() {
+        ParallelConfig::new(0, 100);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(37);
+        for (threads, cutoff) in CONFIGS {
+            let config = ParallelConfig::new(threads, cutoff);
+            for n in [0, 1, 2, 17, 1000, 20_000] {
+                for modulo in [2, 1000, u64::MAX] {
+                    let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                    let mut expected = original.clone();
+                    expected.sort();
+
+                    let mut arr = original.clone();
+                    par_quick_sort_with(&mut arr, config, u64::cmp);
+                    assert_eq!(arr, expected, "{:?}", config);
+
+                    let mut arr = original.clone();
+                    par_merge_sort_with(&mut arr, config, u64::cmp);
+                    assert_eq!(arr, expected, "{:?}", config);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let mut rng = XorShift::new(38);
+        // (key, original position), only the key is compared so there are lots of ties
+        let original: Vec<(u32, usize)> =
+            (0..50_000).map(|i| (rng.range(0, 100) as u32, i)).collect();
+
+        let mut stable = original.clone();
+        stable.sort_by_key(|r| r.0);
+
+        for (threads, cutoff) in CONFIGS {
+            let config = ParallelConfig::new(threads, cutoff);
+
+            let mut first = original.clone();
+            par_quick_sort_with(&mut first, config, |a, b| a.0.cmp(&b.0));
+            for _ in 0..3 {
+                let mut arr = original.clone();
+                par_quick_sort_with(&mut arr, config, |a, b| a.0.cmp(&b.0));
+                assert_eq!(arr, first, "{:?}", config);
+            }
+
+            let mut arr = original.clone();
+            par_merge_sort_with(&mut arr, config, |a, b| a.0.cmp(&b.0));
+            assert_eq!(arr, stable, "{:?}", config);
+        }
+    }
+
+    // cargo test --release bench_ -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_parallel_vs_serial() {
+        let n = 20_000_000;
+        let mut rng = XorShift::new(39);
+        let original: Vec<u64> = (0..n).map(|_| rng.next_u64()).collect();
+
+        let mut arr = original.clone();
+        let start = Instant::now();
+        quick_sort(&mut arr);
+        let serial = start.elapsed();
+        println!("quick_sort: {:?}", serial);
+
+        for threads in [2, 4, 8, 16] {
+            let config = ParallelConfig::new(threads, DEFAULT_CUTOFF);
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            par_quick_sort_with(&mut arr, config, u64::cmp);
+            let elapsed = start.elapsed();
+            println!(
+                "par_quick_sort, {} threads: {:?} ({:.2}x)",
+                threads,
+                elapsed,
+                serial.as_secs_f64() / elapsed.as_secs_f64()
+            );
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            par_merge_sort_with(&mut arr, config, u64::cmp);
+            let elapsed = start.elapsed();
+            println!(
+                "par_merge_sort, {} threads: {:?} ({:.2}x)",
+                threads,
+                elapsed,
+                serial.as_secs_f64() / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
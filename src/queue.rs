@@ -55,10 +55,8 @@ impl<T: Clone> Queue<T> {
     }
 
     pub fn deque(&mut self) -> Option<T> {
-        if self.head.is_none() {
-            // No head -> return
-            return None;
-        }
+        // No head -> return
+        self.head.as_ref()?;
 
         // We're accessing the head value through a map fn but we could just unwrap it from the Option. Works the same
         self.head.take().map(|old_head| {
@@ -80,16 +78,14 @@ impl<T: Clone> Queue<T> {
     }
     pub fn peek(&self) -> Option<T> {
         // Another way of accessing the head value (this time to just return it's copied value
-        match self.head.as_ref() {
-            Some(head) => {
-                // It sucks cause it's just too many operations and in the end I have to wrap it to Some again
-                Some(head.borrow().value.clone())
-            }
-            None => None,
-        }
+        // This looks much better than matching on it, which is just too many operations and in the end wraps it in Some again
+        self.head.as_ref().map(|head| head.borrow().value.clone())
+    }
+}
 
-        // This looks much better
-        // self.head.as_ref().map(|head| head.borrow().value.clone())
+impl<T: Clone> Default for Queue<T> {
+    fn default() -> Self {
+        Queue::new()
     }
 }
 
@@ -1,52 +1,465 @@
-pub fn quick_sort(
-    arr: &mut Vec<i32>,
-    lo: i32, // idx of the lo elem
-    hi: i32, // idx of the hi elem
-) -> () {
-    if lo >= hi {
-        return;
+use crate::heap_sort::heap_sort_tracked;
+use crate::insertion_sort::insertion_sort_tracked;
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use crate::rng::XorShift;
+use std::cmp::Ordering;
+
+// Below this many elements insertion sort beats partitioning
+const INSERTION_THRESHOLD: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PivotStrategy {
+    Last,
+    Random,
+    MedianOfThree,
+    Ninther, // median of three medians of three, for big arrays
+}
+
+pub fn quick_sort<T: Ord>(arr: &mut [T]) {
+    quick_sort_by(arr, T::cmp);
+}
+
+pub fn quick_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    quick_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn quick_sort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    quick_sort_with(arr, PivotStrategy::MedianOfThree, cmp);
+}
+
+pub fn quick_sort_with<T, F>(arr: &mut [T], strategy: PivotStrategy, cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    quick_sort_tracked(Tracked::new(arr, &NoObserver), strategy, cmp);
+}
+
+pub fn quick_sort_tracked<T, F, O>(arr: Tracked<T, O>, strategy: PivotStrategy, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let mut rng = XorShift::from_entropy();
+    sort(arr, strategy, &mut rng, &mut cmp);
+}
+
+fn sort<T, F, O>(mut arr: Tracked<T, O>, strategy: PivotStrategy, rng: &mut XorShift, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    // Recursing only into the smaller side and looping on the bigger one,
+    // so the stack never gets deeper than log2(n) even when the pivots are bad
+    while arr.len() > 1 {
+        let pivot_idx = choose_pivot_tracked(&arr, strategy, rng, cmp);
+        let (lt, gt) = pivot_tracked(&mut arr, pivot_idx, cmp);
+
+        let (left, rest) = arr.split_at(lt);
+        let (_, right) = rest.split_at(gt - lt);
+
+        if left.len() < right.len() {
+            sort(left, strategy, rng, cmp);
+            arr = right;
+        } else {
+            sort(right, strategy, rng, cmp);
+            arr = left;
+        }
     }
+}
 
-    let pivot_idx = pivot(arr, lo, hi);
+pub fn introsort<T: Ord>(arr: &mut [T]) {
+    introsort_by(arr, T::cmp);
+}
 
-    quick_sort(arr, lo, pivot_idx - 1); // hi is exclusive pivot
-    quick_sort(arr, pivot_idx + 1, hi); // lo is exclusive pivot
+pub fn introsort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    introsort_by(arr, |a, b| key(a).cmp(&key(b)));
 }
 
-fn pivot(arr: &mut Vec<i32>, lo: i32, hi: i32) -> i32 {
-    let pivot = arr[hi as usize];
-    let mut idx = lo - 1;
+pub fn introsort_by<T, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    introsort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
 
-    for i in lo..hi {
-        if arr[i as usize] < pivot {
-            idx += 1;
-            arr.swap(i as usize, idx as usize);
+// Quick sort that keeps an eye on its recursion depth. Once it gets past 2*log2(n)
+// the pivots are clearly not working out, so whatever is left goes to heap sort
+// and we stay O(n log n) no matter what the input looks like.
+pub fn introsort_tracked<T, F, O>(arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let depth_limit = 2 * arr.len().max(1).ilog2() as usize;
+    intro(arr, depth_limit, &mut cmp);
+}
+
+fn intro<T, F, O>(mut arr: Tracked<T, O>, mut depth_limit: usize, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    // Ninther doesn't use the rng, it's just here to satisfy choose_pivot
+    let mut rng = XorShift::new(1);
+
+    loop {
+        if arr.len() <= INSERTION_THRESHOLD {
+            insertion_sort_tracked(arr, &mut *cmp);
+            return;
+        }
+
+        if depth_limit == 0 {
+            heap_sort_tracked(arr, &mut *cmp);
+            return;
         }
+        depth_limit -= 1;
+
+        let pivot_idx = choose_pivot_tracked(&arr, PivotStrategy::Ninther, &mut rng, cmp);
+        let (lt, gt) = pivot_tracked(&mut arr, pivot_idx, cmp);
+
+        let (left, rest) = arr.split_at(lt);
+        let (_, right) = rest.split_at(gt - lt);
+
+        if left.len() < right.len() {
+            intro(left, depth_limit, cmp);
+            arr = right;
+        } else {
+            intro(right, depth_limit, cmp);
+            arr = left;
+        }
+    }
+}
+
+pub fn pivot<T, F>(arr: &mut [T], pivot_idx: usize, cmp: &mut F) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    pivot_tracked(&mut Tracked::new(arr, &NoObserver), pivot_idx, cmp)
+}
+
+// Three-way partition around arr[pivot_idx]. Returns (lt, gt) such that
+// arr[..lt] < pivot, arr[lt..gt] == pivot and arr[gt..] > pivot, so runs of
+// duplicates are done after a single pass instead of being partitioned over and over.
+pub fn pivot_tracked<T, F, O>(
+    arr: &mut Tracked<T, O>,
+    pivot_idx: usize,
+    cmp: &mut F,
+) -> (usize, usize)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    arr.swap(0, pivot_idx);
+
+    // arr[lt..i] are all equal to the pivot, so arr[lt] can always stand in for it
+    let mut lt = 0;
+    let mut i = 1;
+    let mut gt = arr.len();
+
+    while i < gt {
+        match arr.compare(i, lt, cmp) {
+            Ordering::Less => {
+                arr.swap(lt, i);
+                lt += 1;
+                i += 1;
+            }
+            Ordering::Greater => {
+                gt -= 1;
+                arr.swap(i, gt);
+            }
+            Ordering::Equal => i += 1,
+        }
+    }
+
+    (lt, gt)
+}
+
+pub fn choose_pivot<T, F>(
+    arr: &mut [T],
+    strategy: PivotStrategy,
+    rng: &mut XorShift,
+    cmp: &mut F,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    choose_pivot_tracked(&Tracked::new(arr, &NoObserver), strategy, rng, cmp)
+}
+
+pub fn choose_pivot_tracked<T, F, O>(
+    arr: &Tracked<T, O>,
+    strategy: PivotStrategy,
+    rng: &mut XorShift,
+    cmp: &mut F,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let len = arr.len();
+    let last = len - 1;
+
+    match strategy {
+        PivotStrategy::Last => last,
+        PivotStrategy::Random => rng.range(0, len),
+        PivotStrategy::Ninther if len >= 40 => {
+            let step = len / 8;
+            let mid = len / 2;
+            let a = median_of_three(arr, 0, step, 2 * step, cmp);
+            let b = median_of_three(arr, mid - step, mid, mid + step, cmp);
+            let c = median_of_three(arr, last - 2 * step, last - step, last, cmp);
+            median_of_three(arr, a, b, c, cmp)
+        }
+        PivotStrategy::MedianOfThree | PivotStrategy::Ninther if len >= 3 => {
+            median_of_three(arr, 0, len / 2, last, cmp)
+        }
+        _ => last,
     }
+}
 
-    idx += 1;
-    arr.swap(hi as usize, idx as usize);
+fn median_of_three<T, F, O>(arr: &Tracked<T, O>, a: usize, b: usize, c: usize, cmp: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let ab = arr.compare(a, b, cmp) == Ordering::Less;
+    let bc = arr.compare(b, c, cmp) == Ordering::Less;
+    if ab == bc {
+        return b;
+    }
 
-    idx
+    let ac = arr.compare(a, c, cmp) == Ordering::Less;
+    if ab == ac {
+        c
+    } else {
+        a
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::thread;
+
+    const STRATEGIES: [PivotStrategy; 4] = [
+        PivotStrategy::Last,
+        PivotStrategy::Random,
+        PivotStrategy::MedianOfThree,
+        PivotStrategy::Ninther,
+    ];
 
     #[test]
     fn test_1() {
         let mut arr = vec![9, 3, 7, 4, 69, 420, 42];
-        let len = arr.len();
-        quick_sort(&mut arr, 0, (len - 1) as i32);
+        quick_sort(&mut arr);
         assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
     }
 
     #[test]
     fn test_2() {
         let mut arr = vec![2, 5, 7, 3, 9, 1, 8, 6, 4];
-        let len = arr.len();
-        quick_sort(&mut arr, 0, (len - 1) as i32);
+        quick_sort(&mut arr);
         assert_eq!(arr, [1, 2, 3, 4, 5, 6, 7, 8, 9]);
     }
+
+    #[test]
+    fn empty_and_single() {
+        let mut arr: Vec<i32> = vec![];
+        quick_sort(&mut arr);
+        assert!(arr.is_empty());
+
+        let mut arr = vec![1];
+        quick_sort(&mut arr);
+        assert_eq!(arr, [1]);
+    }
+
+    #[test]
+    fn by_and_by_key() {
+        let mut arr = ["bb", "a", "dddd", "ccc"];
+        quick_sort_by(&mut arr, |a, b| b.cmp(a));
+        assert_eq!(arr, ["dddd", "ccc", "bb", "a"]);
+
+        quick_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc", "dddd"]);
+    }
+
+    #[test]
+    fn partition() {
+        let mut arr = [5, 1, 5, 9, 3, 5, 7];
+        let (lt, gt) = pivot(&mut arr, 0, &mut i32::cmp);
+        assert_eq!((lt, gt), (2, 5));
+        assert!(arr[..lt].iter().all(|&x| x < 5));
+        assert!(arr[lt..gt].iter().all(|&x| x == 5));
+        assert!(arr[gt..].iter().all(|&x| x > 5));
+    }
+
+    #[test]
+    fn median() {
+        let mut arr = [3, 1, 2];
+        let arr = Tracked::new(&mut arr, &NoObserver);
+        for (a, b, c) in [
+            (0, 1, 2),
+            (0, 2, 1),
+            (1, 0, 2),
+            (1, 2, 0),
+            (2, 0, 1),
+            (2, 1, 0),
+        ] {
+            // 2 is the median and also happens to be at index 2
+            assert_eq!(median_of_three(&arr, a, b, c, &mut i32::cmp), 2);
+        }
+    }
+
+    #[test]
+    fn all_strategies_against_std() {
+        let mut rng = XorShift::new(32);
+        for strategy in STRATEGIES {
+            for n in [0, 1, 2, 3, 10, 39, 40, 41, 100, 1000] {
+                for modulo in [2, 10, 1_000_000] {
+                    let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                    let mut expected = original.clone();
+                    expected.sort();
+
+                    let mut arr = original.clone();
+                    quick_sort_with(&mut arr, strategy, u64::cmp);
+                    assert_eq!(arr, expected, "{:?}", strategy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_and_reversed() {
+        for strategy in STRATEGIES {
+            let mut arr: Vec<u32> = (0..2000).collect();
+            quick_sort_with(&mut arr, strategy, u32::cmp);
+            assert_eq!(arr, (0..2000).collect::<Vec<u32>>());
+
+            arr.reverse();
+            quick_sort_with(&mut arr, strategy, u32::cmp);
+            assert_eq!(arr, (0..2000).collect::<Vec<u32>>());
+        }
+    }
+
+    #[test]
+    fn all_equal_is_linear() {
+        let mut comparisons = 0;
+        let mut arr = vec![7; 10_000];
+        quick_sort_by(&mut arr, |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        assert!(comparisons < 20_000);
+    }
+
+    #[test]
+    fn introsort_against_std() {
+        let mut rng = XorShift::new(33);
+        for n in [0, 1, 2, 15, 16, 17, 100, 1000, 5000] {
+            for modulo in [2, 10, 1_000_000] {
+                let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                let mut expected = original.clone();
+                expected.sort();
+
+                let mut arr = original.clone();
+                introsort(&mut arr);
+                assert_eq!(arr, expected);
+            }
+        }
+
+        let mut arr = ["bb", "a", "dddd", "ccc"];
+        introsort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc", "dddd"]);
+    }
+
+    // McIlroy's "A Killer Adversary for Quicksort". The values of the items are decided
+    // lazily while the sort runs: everything starts out as "gas" (bigger than any solid value)
+    // and an item only gets frozen into a solid value when it's compared to another gas item.
+    // The one frozen is never the likely pivot, so the pivot keeps landing at the edge.
+    struct Adversary {
+        val: Vec<usize>,
+        gas: usize,
+        solid: usize,
+        candidate: usize,
+        comparisons: usize,
+    }
+
+    impl Adversary {
+        fn new(n: usize) -> Adversary {
+            Adversary {
+                val: vec![n; n],
+                gas: n,
+                solid: 0,
+                candidate: 0,
+                comparisons: 0,
+            }
+        }
+
+        fn freeze(&mut self, x: usize) {
+            self.val[x] = self.solid;
+            self.solid += 1;
+        }
+
+        fn cmp(&mut self, x: usize, y: usize) -> Ordering {
+            self.comparisons += 1;
+
+            if self.val[x] == self.gas && self.val[y] == self.gas {
+                if x == self.candidate {
+                    self.freeze(x);
+                } else {
+                    self.freeze(y);
+                }
+            }
+
+            if self.val[x] == self.gas {
+                self.candidate = x;
+            } else if self.val[y] == self.gas {
+                self.candidate = y;
+            }
+
+            self.val[x].cmp(&self.val[y])
+        }
+    }
+
+    #[test]
+    fn antiqsort() {
+        let n: usize = 4096;
+        let n_log_n = n * n.ilog2() as usize;
+
+        // Plain quick sort goes quadratic against the adversary
+        let mut adversary = Adversary::new(n);
+        let mut items: Vec<usize> = (0..n).collect();
+        quick_sort_with(&mut items, PivotStrategy::Ninther, |a, b| {
+            adversary.cmp(*a, *b)
+        });
+        assert!(adversary.comparisons > n * n / 16);
+
+        let mut adversary = Adversary::new(n);
+        let mut items: Vec<usize> = (0..n).collect();
+        introsort_by(&mut items, |a, b| adversary.cmp(*a, *b));
+        assert!(adversary.comparisons < 4 * n_log_n);
+
+        // The adversary still has to answer consistently, so the items do get sorted
+        let val = adversary.val;
+        assert!(items.windows(2).all(|w| val[w[0]] <= val[w[1]]));
+    }
+
+    #[test]
+    fn bounded_stack_depth() {
+        // Last element pivot on sorted input is the worst case: every partition
+        // peels off just one element. Without the loop this would need 5000 frames.
+        thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut arr: Vec<u32> = (0..5000).collect();
+                quick_sort_with(&mut arr, PivotStrategy::Last, u32::cmp);
+                assert_eq!(arr, (0..5000).collect::<Vec<u32>>());
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
 }
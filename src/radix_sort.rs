@@ -0,0 +1,259 @@
+use crate::counting_sort::{permute_tracked, positions};
+use crate::insertion_sort::insertion_sort_tracked;
+use crate::instrumented::{NoObserver, Observer, Tracked};
+
+// Small buckets in the MSD sort are finished with insertion sort
+const MSD_INSERTION_THRESHOLD: usize = 16;
+
+// Integers that can be mapped to a u64 without changing their order
+pub trait RadixKey: Copy {
+    const BYTES: usize;
+
+    fn to_radix(self) -> u64;
+}
+
+macro_rules! unsigned_radix_key {
+    ($($t:ty),*) => {
+        $(impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            fn to_radix(self) -> u64 {
+                self as u64
+            }
+        })*
+    };
+}
+
+// Flipping the sign bit moves the negatives below the positives, after that
+// two's complement already sorts the right way
+macro_rules! signed_radix_key {
+    ($($t:ty => $u:ty),*) => {
+        $(impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            fn to_radix(self) -> u64 {
+                ((self as $u) ^ (1 << (<$u>::BITS - 1))) as u64
+            }
+        })*
+    };
+}
+
+unsigned_radix_key!(u8, u16, u32, u64, usize);
+signed_radix_key!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+pub fn radix_sort<K: RadixKey>(arr: &mut [K]) {
+    radix_sort_by_key(arr, |x| *x);
+}
+
+// LSD radix sort, a byte at a time starting from the least significant one.
+// Every pass is stable, so the order from the earlier (lower) bytes survives.
+pub fn radix_sort_by_key<T, K: RadixKey, F: FnMut(&T) -> K>(arr: &mut [T], key: F) {
+    radix_sort_tracked(Tracked::new(arr, &NoObserver), key);
+}
+
+pub fn radix_sort_tracked<T, K, F, O>(mut arr: Tracked<T, O>, mut key: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+    O: Observer,
+{
+    if arr.len() < 2 {
+        return;
+    }
+
+    let keys: Vec<u64> = (0..arr.len()).map(|i| key(arr.get(i)).to_radix()).collect();
+    sort_radix_keys_tracked(&mut arr, keys, K::BYTES);
+}
+
+// The passes themselves, for keys already turned into u64s. Only the lowest `bytes` bytes
+// of them are looked at.
+pub fn sort_radix_keys_tracked<T, O>(arr: &mut Tracked<T, O>, mut keys: Vec<u64>, bytes: usize)
+where
+    O: Observer,
+{
+    for byte in 0..bytes {
+        let shift = 8 * byte;
+        let digit = |k: u64| ((k >> shift) & 0xFF) as usize;
+
+        let mut counts = [0; 256];
+        for &k in keys.iter() {
+            counts[digit(k)] += 1;
+        }
+
+        // Everything has the same byte here, nothing would move
+        if counts.contains(&arr.len()) {
+            continue;
+        }
+
+        let mut dest = positions(&keys, &mut counts, digit);
+        permute_tracked(arr, &mut keys, &mut dest);
+    }
+}
+
+pub fn msd_radix_sort<T: AsRef<[u8]>>(arr: &mut [T]) {
+    msd_radix_sort_by_key(arr, |x| x.as_ref());
+}
+
+// MSD radix sort for byte strings: buckets by the first byte, then each bucket by the
+// second one and so on. Strings that end get a bucket of their own in front of byte 0.
+pub fn msd_radix_sort_by_key<T, F>(arr: &mut [T], key: F)
+where
+    F: Fn(&T) -> &[u8],
+{
+    msd_radix_sort_tracked(Tracked::new(arr, &NoObserver), key);
+}
+
+pub fn msd_radix_sort_tracked<T, F, O>(arr: Tracked<T, O>, key: F)
+where
+    F: Fn(&T) -> &[u8],
+    O: Observer,
+{
+    msd(arr, 0, &key);
+}
+
+fn msd<T, F, O>(mut arr: Tracked<T, O>, depth: usize, key: &F)
+where
+    F: Fn(&T) -> &[u8],
+    O: Observer,
+{
+    if arr.len() <= MSD_INSERTION_THRESHOLD {
+        insertion_sort_tracked(arr, |a: &T, b: &T| {
+            suffix(a, depth, key).cmp(suffix(b, depth, key))
+        });
+        return;
+    }
+
+    // 0 for "ended", 1 + byte otherwise
+    let mut digits: Vec<u64> = (0..arr.len())
+        .map(|i| key(arr.get(i)).get(depth).map_or(0, |&b| b as u64 + 1))
+        .collect();
+
+    let mut counts = [0; 257];
+    for &d in digits.iter() {
+        counts[d as usize] += 1;
+    }
+
+    // Whatever ended here is done, the rest needs sorting by the next byte
+    let ended = counts[0];
+    let mut starts = counts;
+    let mut dest = positions(&digits, &mut starts, |d| d as usize);
+    permute_tracked(&mut arr, &mut digits, &mut dest);
+
+    let mut lo = ended;
+    for &count in counts[1..].iter() {
+        if count > 1 {
+            msd(arr.slice(lo..lo + count), depth + 1, key);
+        }
+        lo += count;
+    }
+}
+
+// Everything in one bucket agrees on the first `depth` bytes already
+fn suffix<'a, T, F>(x: &'a T, depth: usize, key: &F) -> &'a [u8]
+where
+    F: Fn(&T) -> &[u8],
+{
+    let k = key(x);
+    &k[depth.min(k.len())..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    #[test]
+    fn test() {
+        let mut arr = [9u32, 3, 7, 4, 69, 420, 42];
+        radix_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+    }
+
+    #[test]
+    fn keys_keep_order() {
+        assert!((-1i32).to_radix() < 0i32.to_radix());
+        assert!(i64::MIN.to_radix() < (-1i64).to_radix());
+        assert!(i8::MAX.to_radix() > 0i8.to_radix());
+        assert_eq!(u64::MAX.to_radix(), u64::MAX);
+    }
+
+    fn check<K: RadixKey + Ord + std::fmt::Debug>(mut arr: Vec<K>) {
+        let mut expected = arr.clone();
+        expected.sort();
+        radix_sort(&mut arr);
+        assert_eq!(arr, expected);
+    }
+
+    #[test]
+    fn all_widths_against_std() {
+        let mut rng = XorShift::new(37);
+        for n in [0, 1, 2, 100, 1000] {
+            let raw: Vec<u64> = (0..n).map(|_| rng.next_u64()).collect();
+            check(raw.iter().map(|&x| x as u8).collect());
+            check(raw.iter().map(|&x| x as u16).collect());
+            check(raw.iter().map(|&x| x as u32).collect());
+            check(raw.clone());
+            check(raw.iter().map(|&x| x as usize).collect());
+            check(raw.iter().map(|&x| x as i8).collect());
+            check(raw.iter().map(|&x| x as i16).collect());
+            check(raw.iter().map(|&x| x as i32).collect());
+            check(raw.iter().map(|&x| x as i64).collect());
+            check(raw.iter().map(|&x| x as isize).collect());
+        }
+
+        check(vec![i64::MAX, i64::MIN, 0, -1, 1, i64::MIN + 1]);
+        check(vec![u64::MAX, 0, 1, u64::MAX - 1]);
+    }
+
+    #[test]
+    fn records_by_key() {
+        let mut rng = XorShift::new(38);
+        let mut arr: Vec<(i64, usize)> = (0..2000)
+            .map(|i| ((rng.next_u64() % 100) as i64 - 50, i))
+            .collect();
+        let mut expected = arr.clone();
+        expected.sort();
+
+        radix_sort_by_key(&mut arr, |r| r.0);
+        assert_eq!(arr, expected);
+    }
+
+    #[test]
+    fn msd_strings() {
+        let mut arr = vec![
+            "she", "sells", "", "sea", "shells", "by", "the", "sea", "s", "shore",
+        ];
+        msd_radix_sort(&mut arr);
+        assert_eq!(
+            arr,
+            ["", "by", "s", "sea", "sea", "sells", "she", "shells", "shore", "the"]
+        );
+    }
+
+    #[test]
+    fn msd_against_std() {
+        let mut rng = XorShift::new(39);
+        for n in [0, 1, 16, 17, 500, 3000] {
+            // Small alphabet and short strings, so lots of shared prefixes and duplicates
+            let mut arr: Vec<Vec<u8>> = (0..n)
+                .map(|_| {
+                    (0..rng.range(0, 8))
+                        .map(|_| b'a' + rng.range(0, 3) as u8)
+                        .collect()
+                })
+                .collect();
+            let mut expected = arr.clone();
+            expected.sort();
+
+            msd_radix_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
+
+    #[test]
+    fn msd_records_by_key() {
+        let mut arr = vec![(3, "pear"), (1, "apple"), (2, "fig"), (4, "apple")];
+        msd_radix_sort_by_key(&mut arr, |r| r.1.as_bytes());
+        assert_eq!(arr, [(1, "apple"), (4, "apple"), (2, "fig"), (3, "pear")]);
+    }
+}
@@ -0,0 +1,511 @@
+use crate::ordered_map::{self, BinaryNode, OrderedMap};
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::RangeBounds;
+
+type Link<K, V> = Option<Box<RbNode<K, V>>>;
+
+struct RbNode<K, V> {
+    key: K,
+    value: V,
+    red: bool, // colour of the link from the parent
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+impl<K, V> RbNode<K, V> {
+    fn new(key: K, value: V) -> Box<RbNode<K, V>> {
+        Box::new(RbNode {
+            key,
+            value,
+            red: true,
+            left: None,
+            right: None,
+        })
+    }
+}
+
+impl<K, V> BinaryNode<K, V> for RbNode<K, V> {
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn value(&self) -> &V {
+        &self.value
+    }
+
+    fn left(&self) -> Option<&Self> {
+        self.left.as_deref()
+    }
+
+    fn right(&self) -> Option<&Self> {
+        self.right.as_deref()
+    }
+}
+
+// Sedgewick's left-leaning red-black tree, a 2-3 tree drawn as a binary one: a red node is
+// glued to its parent into a 3-node. Red links only lean left and never come two in a row,
+// and every path down has the same number of black links, so it's at most 2 * log2(n) deep.
+pub struct RbMap<K: Ord, V> {
+    root: Link<K, V>,
+    length: usize,
+}
+
+impl<K: Ord, V> RbMap<K, V> {
+    pub fn new() -> RbMap<K, V> {
+        RbMap {
+            root: None,
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut old = None;
+        let mut root = insert(self.root.take(), key, value, &mut old);
+        root.red = false;
+        self.root = Some(root);
+
+        if old.is_none() {
+            self.length += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        // Deleting on the way down only works when the key is really there
+        if !self.contains_key(key) {
+            return None;
+        }
+
+        let mut root = self.root.take().unwrap();
+        if !is_red(&root.left) && !is_red(&root.right) {
+            root.red = true;
+        }
+
+        let mut removed = None;
+        self.root = remove(root, key, &mut removed);
+        if let Some(root) = self.root.as_mut() {
+            root.red = false;
+        }
+
+        self.length -= 1;
+        removed
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        ordered_map::get(self.root.as_deref(), key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        ordered_map::min(self.root.as_deref())
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        ordered_map::max(self.root.as_deref())
+    }
+
+    // Biggest key that's <= key
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::floor(self.root.as_deref(), key)
+    }
+
+    // Smallest key that's >= key
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        ordered_map::ceiling(self.root.as_deref(), key)
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        ordered_map::range(self.root.as_deref(), range)
+    }
+
+    // Left, node, right: sorted by key
+    pub fn in_order(&self) -> Vec<(&K, &V)> {
+        self.range(..)
+    }
+
+    pub fn pre_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::pre_order(self.root.as_deref())
+    }
+
+    pub fn post_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::post_order(self.root.as_deref())
+    }
+
+    pub fn level_order(&self) -> Vec<(&K, &V)> {
+        ordered_map::level_order(self.root.as_deref())
+    }
+
+    // Nodes on the longest path from the root down, 0 when empty
+    pub fn height(&self) -> usize {
+        ordered_map::height(self.root.as_deref())
+    }
+
+    // Goes through the whole tree, for tests
+    pub fn check(&self) -> Result<(), String> {
+        if is_red(&self.root) {
+            return Err("Red root".to_string());
+        }
+
+        let mut count = 0;
+        check(&self.root, None, None, &mut count)?;
+        if count != self.length {
+            return Err(format!("Has {} nodes but length is {}", count, self.length));
+        }
+        Ok(())
+    }
+}
+
+impl<K: Ord, V> Default for RbMap<K, V> {
+    fn default() -> Self {
+        RbMap::new()
+    }
+}
+
+impl<K: Ord, V> OrderedMap<K, V> for RbMap<K, V> {
+    fn len(&self) -> usize {
+        RbMap::len(self)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        RbMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        RbMap::remove(self, key)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        RbMap::get(self, key)
+    }
+
+    fn min(&self) -> Option<(&K, &V)> {
+        RbMap::min(self)
+    }
+
+    fn max(&self) -> Option<(&K, &V)> {
+        RbMap::max(self)
+    }
+
+    fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        RbMap::floor(self, key)
+    }
+
+    fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        RbMap::ceiling(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Vec<(&K, &V)> {
+        RbMap::range(self, range)
+    }
+
+    fn in_order(&self) -> Vec<(&K, &V)> {
+        RbMap::in_order(self)
+    }
+
+    fn pre_order(&self) -> Vec<(&K, &V)> {
+        RbMap::pre_order(self)
+    }
+
+    fn post_order(&self) -> Vec<(&K, &V)> {
+        RbMap::post_order(self)
+    }
+
+    fn level_order(&self) -> Vec<(&K, &V)> {
+        RbMap::level_order(self)
+    }
+
+    fn height(&self) -> usize {
+        RbMap::height(self)
+    }
+}
+
+fn is_red<K, V>(link: &Link<K, V>) -> bool {
+    link.as_ref().is_some_and(|node| node.red)
+}
+
+// A right leaning red link turned into a left leaning one, colours stay where they were
+fn rotate_left<K, V>(mut node: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    right.red = node.red;
+    node.red = true;
+    right.left = Some(node);
+    right
+}
+
+fn rotate_right<K, V>(mut node: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    left.red = node.red;
+    node.red = true;
+    left.right = Some(node);
+    left
+}
+
+// Splits a 4-node by passing the middle up, or the other way round when deleting
+fn flip_colors<K, V>(node: &mut RbNode<K, V>) {
+    node.red = !node.red;
+    for child in [&mut node.left, &mut node.right].into_iter().flatten() {
+        child.red = !child.red;
+    }
+}
+
+// On the way back up, puts back what insert or remove bent out of shape
+fn fix_up<K, V>(mut node: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+    if is_red(&node.right) && !is_red(&node.left) {
+        node = rotate_left(node);
+    }
+    if is_red(&node.left) && is_red(&node.left.as_ref().unwrap().left) {
+        node = rotate_right(node);
+    }
+    if is_red(&node.left) && is_red(&node.right) {
+        flip_colors(&mut node);
+    }
+    node
+}
+
+fn insert<K: Ord, V>(link: Link<K, V>, key: K, value: V, old: &mut Option<V>) -> Box<RbNode<K, V>> {
+    let Some(mut node) = link else {
+        return RbNode::new(key, value);
+    };
+
+    match key.cmp(&node.key) {
+        Ordering::Less => node.left = Some(insert(node.left.take(), key, value, old)),
+        Ordering::Greater => node.right = Some(insert(node.right.take(), key, value, old)),
+        Ordering::Equal => *old = Some(mem::replace(&mut node.value, value)),
+    }
+
+    fix_up(node)
+}
+
+// Deleting from a 2-node would leave a hole, so on the way down the node we go into is
+// made part of a 3- or 4-node, borrowing from its sibling or merging with it.
+fn move_red_left<K, V>(mut node: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+    flip_colors(&mut node);
+    if is_red(&node.right.as_ref().unwrap().left) {
+        node.right = Some(rotate_right(node.right.take().unwrap()));
+        node = rotate_left(node);
+        flip_colors(&mut node);
+    }
+    node
+}
+
+fn move_red_right<K, V>(mut node: Box<RbNode<K, V>>) -> Box<RbNode<K, V>> {
+    flip_colors(&mut node);
+    if is_red(&node.left.as_ref().unwrap().left) {
+        node = rotate_right(node);
+        flip_colors(&mut node);
+    }
+    node
+}
+
+// (what's left of the subtree, its smallest node)
+fn remove_min<K, V>(mut node: Box<RbNode<K, V>>) -> (Link<K, V>, Box<RbNode<K, V>>) {
+    // Nothing hangs on the right of the smallest one either, it would have to be red
+    if node.left.is_none() {
+        return (None, node);
+    }
+
+    if !is_red(&node.left) && !is_red(&node.left.as_ref().unwrap().left) {
+        node = move_red_left(node);
+    }
+
+    let (rest, min) = remove_min(node.left.take().unwrap());
+    node.left = rest;
+    (Some(fix_up(node)), min)
+}
+
+// The key has to be in the tree
+fn remove<K: Ord, V>(mut node: Box<RbNode<K, V>>, key: &K, removed: &mut Option<V>) -> Link<K, V> {
+    if *key < node.key {
+        if !is_red(&node.left) && !is_red(&node.left.as_ref().unwrap().left) {
+            node = move_red_left(node);
+        }
+        node.left = remove(node.left.take().unwrap(), key, removed);
+    } else {
+        if is_red(&node.left) {
+            node = rotate_right(node);
+        }
+        // A leaf at the bottom, red by now, can just go
+        if *key == node.key && node.right.is_none() {
+            *removed = Some(node.value);
+            return None;
+        }
+        if !is_red(&node.right) && !is_red(&node.right.as_ref().unwrap().left) {
+            node = move_red_right(node);
+        }
+
+        if *key == node.key {
+            // Swapping in the successor, like in BstMap, and deleting that one instead
+            let (rest, mut successor) = remove_min(node.right.take().unwrap());
+            mem::swap(&mut node.key, &mut successor.key);
+            mem::swap(&mut node.value, &mut successor.value);
+            *removed = Some(successor.value);
+            node.right = rest;
+        } else {
+            node.right = remove(node.right.take().unwrap(), key, removed);
+        }
+    }
+
+    Some(fix_up(node))
+}
+
+// Every key between lo and hi, only left leaning reds and never two in a row. Gives back
+// the number of black links on every path down, which has to be the same everywhere.
+fn check<K: Ord, V>(
+    link: &Link<K, V>,
+    lo: Option<&K>,
+    hi: Option<&K>,
+    count: &mut usize,
+) -> Result<usize, String> {
+    let Some(node) = link else {
+        return Ok(0);
+    };
+    *count += 1;
+
+    if lo.is_some_and(|lo| node.key <= *lo) || hi.is_some_and(|hi| node.key >= *hi) {
+        return Err("Keys out of order".to_string());
+    }
+    if is_red(&node.right) {
+        return Err("Red link leaning right".to_string());
+    }
+    if node.red && is_red(&node.left) {
+        return Err("Two red links in a row".to_string());
+    }
+
+    let left = check(&node.left, lo, Some(&node.key), count)?;
+    let right = check(&node.right, Some(&node.key), hi, count)?;
+    if left != right {
+        return Err(format!("Black heights {} and {} differ", left, right));
+    }
+
+    Ok(left + usize::from(!node.red))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avl_tree::AvlMap;
+    use crate::bst::BstMap;
+    use crate::rng::XorShift;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test() {
+        let mut map = RbMap::new();
+        for key in 0..1000 {
+            assert_eq!(map.insert(key, key * 10), None);
+        }
+        map.check().unwrap();
+        assert!(map.height() <= 20);
+
+        assert_eq!(map.insert(5, 0), Some(50));
+        assert_eq!(map.get(&5), Some(&0));
+
+        for key in (0..1000).step_by(2) {
+            assert_eq!(map.remove(&key), Some(key * 10));
+            map.check().unwrap();
+        }
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.remove(&0), None);
+        assert_eq!(map.min(), Some((&1, &10)));
+
+        for key in (1..1000).step_by(2) {
+            map.remove(&key);
+        }
+        assert!(map.is_empty());
+        map.check().unwrap();
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut rng = XorShift::new(51);
+        let mut map = RbMap::new();
+        let mut expected = BTreeMap::new();
+
+        for _ in 0..20000 {
+            let key = rng.range(0, 300);
+            match rng.range(0, 3) {
+                0 | 1 => assert_eq!(map.insert(key, key * 2), expected.insert(key, key * 2)),
+                _ => assert_eq!(map.remove(&key), expected.remove(&key)),
+            }
+            map.check().unwrap();
+            assert_eq!(map.len(), expected.len());
+            assert_eq!(map.get(&key), expected.get(&key));
+            assert_eq!(map.floor(&key), expected.range(..=key).next_back());
+            assert_eq!(map.ceiling(&key), expected.range(key..).next());
+        }
+
+        let all: Vec<_> = expected.iter().collect();
+        assert_eq!(map.in_order(), all);
+    }
+
+    // Anything that works through the trait works on all three
+    fn fill<M: OrderedMap<u64, u64>>(map: &mut M, keys: &[u64]) -> usize {
+        for &key in keys {
+            map.insert(key, key);
+        }
+        for &key in keys.iter().step_by(3) {
+            map.remove(&key);
+        }
+        map.height()
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let keys: Vec<u64> = (0..3000).collect();
+        let plain = fill(&mut BstMap::new(), &keys);
+        let avl = fill(&mut AvlMap::new(), &keys);
+        let rb = fill(&mut RbMap::new(), &keys);
+
+        // Log2 of 3000 is under 12
+        assert!(plain > 1000);
+        assert!(avl <= 17, "{}", avl);
+        assert!(rb <= 24, "{}", rb);
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_ordered_maps() {
+        // cargo test --release bench_ -- --ignored --nocapture
+        use std::time::Instant;
+
+        let mut rng = XorShift::new(52);
+        let mut random: Vec<u64> = (0..200_000).collect();
+        rng.shuffle(&mut random);
+        let sorted: Vec<u64> = (0..200_000).collect();
+
+        for (name, keys) in [("random", &random), ("sorted", &sorted)] {
+            let start = Instant::now();
+            let height = fill(&mut AvlMap::new(), keys);
+            println!("{} avl: {:?}, height {}", name, start.elapsed(), height);
+
+            let start = Instant::now();
+            let height = fill(&mut RbMap::new(), keys);
+            println!(
+                "{} red-black: {:?}, height {}",
+                name,
+                start.elapsed(),
+                height
+            );
+
+            if name == "random" {
+                let start = Instant::now();
+                let height = fill(&mut BstMap::new(), keys);
+                println!("{} plain: {:?}, height {}", name, start.elapsed(), height);
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// Tiny xorshift generator, good enough for shuffling test data and picking pivots
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // Zero state would get stuck at zero forever
+        XorShift {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn from_entropy() -> XorShift {
+        // RandomState is seeded from the OS, so hashing nothing gives us a random number
+        XorShift::new(RandomState::new().build_hasher().finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    // Random number in lo..hi
+    pub fn range(&mut self, lo: usize, hi: usize) -> usize {
+        assert!(lo < hi, "Range is empty");
+        lo + (self.next_u64() % (hi - lo) as u64) as usize
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn shuffle<T>(&mut self, arr: &mut [T]) {
+        for i in (1..arr.len()).rev() {
+            let j = self.range(0, i + 1);
+            arr.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        let mut rng = XorShift::new(42);
+        for _ in 0..1000 {
+            let x = rng.range(3, 7);
+            assert!((3..7).contains(&x));
+        }
+    }
+
+    #[test]
+    fn shuffle_keeps_elements() {
+        let mut rng = XorShift::new(7);
+        let mut arr: Vec<u32> = (0..100).collect();
+        rng.shuffle(&mut arr);
+        assert_ne!(arr, (0..100).collect::<Vec<u32>>());
+        arr.sort();
+        assert_eq!(arr, (0..100).collect::<Vec<u32>>());
+    }
+}
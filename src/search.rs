@@ -0,0 +1,404 @@
+use crate::binary_search_list::{binary_search, lower_bound};
+use std::ops::RangeInclusive;
+
+// Searches over sorted slices. They all answer the same way binary_search does: Ok with
+// the first index of the needle, or Err with where it would have to be inserted.
+pub trait Searcher<T> {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize>;
+}
+
+pub struct Binary;
+pub struct Exponential;
+pub struct Jump;
+pub struct Fibonacci;
+pub struct Interpolation;
+
+impl<T: Ord> Searcher<T> for Binary {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize> {
+        binary_search(arr, needle)
+    }
+}
+
+impl<T: Ord> Searcher<T> for Exponential {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize> {
+        exponential_search(arr, needle)
+    }
+}
+
+impl<T: Ord> Searcher<T> for Jump {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize> {
+        jump_search(arr, needle)
+    }
+}
+
+impl<T: Ord> Searcher<T> for Fibonacci {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize> {
+        fibonacci_search(arr, needle)
+    }
+}
+
+impl<T: Ord + Interpolate> Searcher<T> for Interpolation {
+    fn search(&self, arr: &[T], needle: &T) -> Result<usize, usize> {
+        interpolation_search(arr, needle)
+    }
+}
+
+// i is the lower bound of the needle, turns it into what binary_search would return
+fn found<T: Ord>(arr: &[T], i: usize, needle: &T) -> Result<usize, usize> {
+    if i < arr.len() && arr[i] == *needle {
+        Ok(i)
+    } else {
+        Err(i)
+    }
+}
+
+// Doubles the bound until it's past the needle, then binary searches the last stretch.
+// O(log i) where i is where the needle is, so cheap when it's near the front.
+pub fn exponential_search<T: Ord>(arr: &[T], needle: &T) -> Result<usize, usize> {
+    let mut bound = 1;
+    while bound < arr.len() && arr[bound - 1] < *needle {
+        bound *= 2;
+    }
+
+    // arr[bound / 2 - 1] < needle, and arr[bound - 1] isn't (or doesn't exist)
+    let lo = bound / 2;
+    let hi = bound.min(arr.len());
+    let i = lo + lower_bound(&arr[lo..hi], needle);
+    found(arr, i, needle)
+}
+
+// The same for sorted data whose length isn't known up front, like a stream or a huge
+// file. get(i) returns None past the end.
+pub fn exponential_search_unbounded<T: Ord, G>(mut get: G, needle: &T) -> Result<usize, usize>
+where
+    G: FnMut(usize) -> Option<T>,
+{
+    let mut less = |i: usize| get(i).is_some_and(|x| x < *needle);
+
+    let mut bound = 1;
+    while less(bound - 1) {
+        bound *= 2;
+    }
+
+    let mut lo = bound / 2;
+    let mut hi = bound - 1;
+    while lo < hi {
+        let m = lo + (hi - lo) / 2;
+        if less(m) {
+            lo = m + 1;
+        } else {
+            hi = m;
+        }
+    }
+
+    match get(lo) {
+        Some(x) if x == *needle => Ok(lo),
+        _ => Err(lo),
+    }
+}
+
+// Jumps sqrt(n) at a time until a block ends past the needle, then walks that block.
+// Same idea as two_crystal_balls, O(sqrt(n)).
+pub fn jump_search<T: Ord>(arr: &[T], needle: &T) -> Result<usize, usize> {
+    let step = ((arr.len() as f64).sqrt() as usize).max(1);
+
+    let mut lo = 0;
+    while lo + step <= arr.len() && arr[lo + step - 1] < *needle {
+        lo += step;
+    }
+
+    let hi = (lo + step).min(arr.len());
+    let i = lo + arr[lo..hi].iter().take_while(|x| *x < needle).count();
+    found(arr, i, needle)
+}
+
+// Binary search that splits at Fibonacci numbers instead of the middle, so finding
+// the next probe only takes additions and subtractions.
+pub fn fibonacci_search<T: Ord>(arr: &[T], needle: &T) -> Result<usize, usize> {
+    let mut lo = 0;
+    let mut hi = arr.len();
+
+    // Two consecutive Fibonacci numbers, b at least as long as the array
+    let (mut a, mut b) = (1, 1);
+    while b < hi {
+        (a, b) = (b, a + b);
+    }
+
+    // The lower bound is somewhere in lo..=hi
+    while lo < hi {
+        // Shrinking down the sequence until the probe lands inside, stops at (1, 1) at worst
+        while a > hi - lo {
+            (a, b) = (b - a, a);
+        }
+
+        let p = lo + a - 1;
+        if arr[p] < *needle {
+            lo = p + 1;
+        } else {
+            hi = p;
+        }
+    }
+
+    found(arr, lo, needle)
+}
+
+// Numbers that interpolation search can guess positions from
+pub trait Interpolate {
+    fn to_f64(&self) -> f64;
+}
+
+macro_rules! interpolate {
+    ($($t:ty),*) => {
+        $(impl Interpolate for $t {
+            fn to_f64(&self) -> f64 {
+                *self as f64
+            }
+        })*
+    };
+}
+
+interpolate!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+// Guesses where the needle should be from its value, assuming the values are spread out
+// evenly. O(log log n) on uniform keys, but O(n) when they're skewed. The guess only ever
+// picks the probe, the comparisons are exact, so precision loss in to_f64 can't make it
+// return a wrong answer.
+pub fn interpolation_search<T: Ord + Interpolate>(arr: &[T], needle: &T) -> Result<usize, usize> {
+    let mut lo = 0;
+    let mut hi = arr.len();
+
+    while lo < hi {
+        if *needle <= arr[lo] {
+            break;
+        }
+        if *needle > arr[hi - 1] {
+            lo = hi;
+            break;
+        }
+
+        // arr[lo] < needle <= arr[hi - 1] from here
+        let first = arr[lo].to_f64();
+        let last = arr[hi - 1].to_f64();
+        let fraction = (needle.to_f64() - first) / (last - first);
+        // first == last after rounding gives NaN or infinity, the cast and min() clamp those
+        let p = (lo + (fraction * (hi - 1 - lo) as f64) as usize).min(hi - 1);
+
+        if arr[p] < *needle {
+            lo = p + 1;
+        } else {
+            hi = p;
+        }
+    }
+
+    found(arr, lo, needle)
+}
+
+// Where a function that goes up and then down peaks, looked for in lo..hi. Each step
+// throws away a third of the interval, so it takes about 1.7 times as many steps as
+// halving would.
+pub fn ternary_search_max<F>(lo: f64, hi: f64, eps: f64, max_iterations: usize, mut f: F) -> f64
+where
+    F: FnMut(f64) -> f64,
+{
+    let (mut lo, mut hi) = (lo, hi);
+    for _ in 0..max_iterations {
+        if hi - lo <= eps {
+            break;
+        }
+
+        let third = (hi - lo) / 3.0;
+        let (m1, m2) = (lo + third, hi - third);
+        if f(m1) < f(m2) {
+            lo = m1;
+        } else {
+            hi = m2;
+        }
+    }
+
+    lo / 2.0 + hi / 2.0
+}
+
+// Same over integers. f has to be strictly increasing and then strictly decreasing,
+// with flat stretches there's no telling which side the peak is on.
+pub fn ternary_search_max_i64<K: Ord, F>(range: RangeInclusive<i64>, mut f: F) -> i64
+where
+    F: FnMut(i64) -> K,
+{
+    let (mut lo, mut hi) = range.into_inner();
+    if lo > hi {
+        panic!("Empty range");
+    }
+
+    while hi.abs_diff(lo) > 2 {
+        let third = (hi.abs_diff(lo) / 3) as i64;
+        let (m1, m2) = (lo + third, hi - third);
+        if f(m1) < f(m2) {
+            lo = m1 + 1;
+        } else {
+            hi = m2;
+        }
+    }
+
+    // At most three left. lo + 1 would overflow when lo is i64::MAX
+    let mut best = lo;
+    for x in (lo..=hi).skip(1) {
+        if f(x) > f(best) {
+            best = x;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+    use std::cmp::Reverse;
+    use std::time::Instant;
+
+    fn searchers() -> Vec<(&'static str, Box<dyn Searcher<u64>>)> {
+        vec![
+            ("binary", Box::new(Binary)),
+            ("exponential", Box::new(Exponential)),
+            ("jump", Box::new(Jump)),
+            ("fibonacci", Box::new(Fibonacci)),
+            ("interpolation", Box::new(Interpolation)),
+        ]
+    }
+
+    #[test]
+    fn test() {
+        let arr = [1u64, 3, 3, 3, 5, 8, 13, 21];
+        for (name, searcher) in searchers() {
+            assert_eq!(searcher.search(&arr, &3), Ok(1), "{}", name);
+            assert_eq!(searcher.search(&arr, &21), Ok(7), "{}", name);
+            assert_eq!(searcher.search(&arr, &0), Err(0), "{}", name);
+            assert_eq!(searcher.search(&arr, &4), Err(4), "{}", name);
+            assert_eq!(searcher.search(&arr, &22), Err(8), "{}", name);
+            assert_eq!(searcher.search(&[], &1), Err(0), "{}", name);
+        }
+    }
+
+    #[test]
+    fn against_binary_search() {
+        let mut rng = XorShift::new(43);
+        for n in (0..40).chain([100, 1000, 4321]) {
+            for modulo in [3, 50, u64::MAX] {
+                let mut arr: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                arr.sort();
+
+                let mut needles: Vec<u64> = arr.clone();
+                needles.extend((0..50).map(|_| rng.next_u64() % modulo));
+                needles.extend([0, u64::MAX]);
+
+                for needle in needles {
+                    let expected = binary_search(&arr, &needle);
+                    for (name, searcher) in searchers() {
+                        assert_eq!(searcher.search(&arr, &needle), expected, "{}", name);
+                    }
+
+                    let streamed = exponential_search_unbounded(|i| arr.get(i).copied(), &needle);
+                    assert_eq!(streamed, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn signed_interpolation() {
+        let arr = [i64::MIN, -5, -5, 0, 7, i64::MAX];
+        for (i, x) in arr.iter().enumerate() {
+            assert_eq!(
+                interpolation_search(&arr, x),
+                binary_search(&arr, x),
+                "{}",
+                i
+            );
+        }
+        assert_eq!(interpolation_search(&arr, &1), Err(4));
+    }
+
+    #[test]
+    fn unbounded() {
+        // Squares, "generated" as they're asked for
+        let mut probes = 0;
+        let found = exponential_search_unbounded(
+            |i| {
+                probes += 1;
+                Some(i as u64 * i as u64)
+            },
+            &(1000 * 1000),
+        );
+        assert_eq!(found, Ok(1000));
+        assert!(probes < 25);
+
+        let found = exponential_search_unbounded(|i| Some(i as u64 * i as u64), &1001);
+        assert_eq!(found, Err(32));
+    }
+
+    #[test]
+    fn ternary() {
+        let peak = ternary_search_max(-10.0, 10.0, 1e-9, 200, |x| -(x - 3.7) * (x - 3.7));
+        assert!((peak - 3.7).abs() < 1e-6);
+
+        let peak = ternary_search_max_i64(-1_000_000..=1_000_000, |x| -(x - 1000) * (x - 1000));
+        assert_eq!(peak, 1000);
+
+        assert_eq!(ternary_search_max_i64(5..=5, |x| x), 5);
+        assert_eq!(ternary_search_max_i64(0..=10, |x| x), 10);
+        assert_eq!(ternary_search_max_i64(0..=10, |x| -x), 0);
+        let peak = ternary_search_max_i64(i64::MIN..=i64::MAX, |x| Reverse(x.abs_diff(7)));
+        assert_eq!(peak, 7);
+    }
+
+    #[test]
+    fn ternary_type_extremes() {
+        assert_eq!(ternary_search_max_i64(i64::MAX..=i64::MAX, |x| x), i64::MAX);
+        assert_eq!(
+            ternary_search_max_i64(i64::MAX - 2..=i64::MAX, |x| x),
+            i64::MAX
+        );
+        assert_eq!(
+            ternary_search_max_i64(i64::MAX - 2..=i64::MAX, Reverse),
+            i64::MAX - 2
+        );
+        assert_eq!(ternary_search_max_i64(i64::MIN..=i64::MIN, |x| x), i64::MIN);
+        assert_eq!(
+            ternary_search_max_i64(i64::MIN..=i64::MIN + 2, Reverse),
+            i64::MIN
+        );
+        assert_eq!(ternary_search_max_i64(i64::MIN..=i64::MAX, |x| x), i64::MAX);
+        assert_eq!(
+            ternary_search_max_i64(i64::MIN..=i64::MAX, Reverse),
+            i64::MIN
+        );
+    }
+
+    // cargo test --release bench_ -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_searchers() {
+        let n = 10_000_000;
+        let lookups = 1_000_000;
+        let mut rng = XorShift::new(44);
+
+        let mut uniform: Vec<u64> = (0..n).map(|_| rng.next_u64() >> 8).collect();
+        uniform.sort();
+        // Squares bunch up at the front, which interpolation search doesn't like
+        let skewed: Vec<u64> = (0..n as u64).map(|i| i * i).collect();
+
+        for (data, arr) in [("uniform", &uniform), ("skewed", &skewed)] {
+            let needles: Vec<u64> = (0..lookups).map(|_| arr[rng.range(0, arr.len())]).collect();
+
+            for (name, searcher) in searchers() {
+                let start = Instant::now();
+                let mut hits = 0;
+                for needle in needles.iter() {
+                    hits += searcher.search(arr, needle).is_ok() as usize;
+                }
+                assert_eq!(hits, lookups);
+                println!("{} {}: {:?}", data, name, start.elapsed());
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use crate::heap_sort::{heap_sort_by, sift_down};
+use crate::insertion_sort::insertion_sort_tracked;
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use crate::quick_sort::{choose_pivot_tracked, introsort_tracked, pivot_tracked, PivotStrategy};
+use crate::rng::XorShift;
+use std::cmp::Ordering;
+
+// Below this many elements the rest is just insertion sorted
+const INSERTION_THRESHOLD: usize = 16;
+
+pub fn select_nth<T: Ord>(arr: &mut [T], k: usize) -> &T {
+    select_nth_by(arr, k, T::cmp)
+}
+
+pub fn select_nth_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], k: usize, mut key: F) -> &T {
+    select_nth_by(arr, k, |a, b| key(a).cmp(&key(b)))
+}
+
+// Quickselect. Moves the element that would be at index k after sorting to index k,
+// with nothing bigger before it and nothing smaller after it. Only follows the side of
+// each partition that k is on, so O(n) on average but still O(n^2) on a bad day.
+pub fn select_nth_by<T, F>(arr: &mut [T], k: usize, cmp: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    select_nth_tracked(Tracked::new(arr, &NoObserver), k, cmp);
+    &arr[k]
+}
+
+pub fn select_nth_tracked<T, F, O>(arr: Tracked<T, O>, k: usize, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if k >= arr.len() {
+        panic!("Index out of bounds");
+    }
+
+    // Ninther doesn't use the rng
+    let mut rng = XorShift::new(1);
+    select(arr, k, &mut cmp, |arr, cmp| {
+        choose_pivot_tracked(arr, PivotStrategy::Ninther, &mut rng, cmp)
+    });
+}
+
+pub fn select_nth_linear<T: Ord>(arr: &mut [T], k: usize) -> &T {
+    select_nth_linear_by(arr, k, T::cmp)
+}
+
+pub fn select_nth_linear_by_key<T, K: Ord, F: FnMut(&T) -> K>(
+    arr: &mut [T],
+    k: usize,
+    mut key: F,
+) -> &T {
+    select_nth_linear_by(arr, k, |a, b| key(a).cmp(&key(b)))
+}
+
+// Same as select_nth_by, but the pivot is the median of medians of groups of five, which
+// is always somewhere between the 30th and 70th percentile. That makes it O(n) in the
+// worst case, at the price of a much bigger constant than quickselect.
+pub fn select_nth_linear_by<T, F>(arr: &mut [T], k: usize, cmp: F) -> &T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    select_nth_linear_tracked(Tracked::new(arr, &NoObserver), k, cmp);
+    &arr[k]
+}
+
+pub fn select_nth_linear_tracked<T, F, O>(arr: Tracked<T, O>, k: usize, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    if k >= arr.len() {
+        panic!("Index out of bounds");
+    }
+
+    linear(arr, k, &mut cmp);
+}
+
+fn linear<T, F, O>(arr: Tracked<T, O>, k: usize, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    select(arr, k, cmp, median_of_medians);
+}
+
+fn select<T, F, O, P>(mut arr: Tracked<T, O>, mut k: usize, cmp: &mut F, mut choose: P)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+    P: FnMut(&mut Tracked<T, O>, &mut F) -> usize,
+{
+    loop {
+        if arr.len() <= INSERTION_THRESHOLD {
+            insertion_sort_tracked(arr, &mut *cmp);
+            return;
+        }
+
+        let pivot_idx = choose(&mut arr, cmp);
+        let (lt, gt) = pivot_tracked(&mut arr, pivot_idx, cmp);
+
+        if k < lt {
+            arr = arr.split_at(lt).0;
+        } else if k >= gt {
+            arr = arr.split_at(gt).1;
+            k -= gt;
+        } else {
+            // k landed among the copies of the pivot
+            return;
+        }
+    }
+}
+
+// Sorts every group of five to find its median and moves the medians to the front,
+// then selects the median of those. Returns where it ended up.
+fn median_of_medians<T, F, O>(arr: &mut Tracked<T, O>, cmp: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let groups = arr.len() / 5;
+    for g in 0..groups {
+        insertion_sort_tracked(arr.slice(5 * g..5 * g + 5), &mut *cmp);
+        // g is in a group that's already been looked at, so nothing gets lost
+        arr.swap(g, 5 * g + 2);
+    }
+
+    let mid = groups / 2;
+    linear(arr.slice(0..groups), mid, cmp);
+    mid
+}
+
+pub fn partial_sort<T: Ord>(arr: &mut [T], k: usize) {
+    partial_sort_by(arr, k, T::cmp);
+}
+
+pub fn partial_sort_by_key<T, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], k: usize, mut key: F) {
+    partial_sort_by(arr, k, |a, b| key(a).cmp(&key(b)));
+}
+
+// Puts the k smallest elements at the front in sorted order, the rest end up after them
+// in no particular order. O(n + k log k). k past the end just sorts everything.
+pub fn partial_sort_by<T, F>(arr: &mut [T], k: usize, cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    partial_sort_tracked(Tracked::new(arr, &NoObserver), k, cmp);
+}
+
+pub fn partial_sort_tracked<T, F, O>(mut arr: Tracked<T, O>, k: usize, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let k = k.min(arr.len());
+    if k == 0 {
+        return;
+    }
+
+    if k < arr.len() {
+        select_nth_tracked(arr.reborrow(), k - 1, &mut cmp);
+    }
+    introsort_tracked(arr.slice(0..k), cmp);
+}
+
+pub fn top_k<T: Ord, I: IntoIterator<Item = T>>(iter: I, k: usize) -> Vec<T> {
+    let mut top = TopK::new(k);
+    top.extend(iter);
+    top.into_sorted_vec()
+}
+
+pub fn top_k_by<T, I, F>(iter: I, k: usize, cmp: F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut top = TopK::with_cmp(k, cmp);
+    top.extend(iter);
+    top.into_sorted_vec()
+}
+
+// Keeps the k biggest elements seen so far in a min heap, so the smallest of them is
+// on top and is the one that gets replaced. O(log k) per element and never more than
+// k of them in memory, whatever the size of the stream.
+pub struct TopK<T, F> {
+    heap: Vec<T>,
+    k: usize,
+    cmp: F,
+}
+
+impl<T: Ord> TopK<T, fn(&T, &T) -> Ordering> {
+    pub fn new(k: usize) -> Self {
+        TopK::with_cmp(k, T::cmp)
+    }
+}
+
+impl<T, F> TopK<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    pub fn with_cmp(k: usize, cmp: F) -> Self {
+        TopK {
+            heap: Vec::with_capacity(k),
+            k,
+            cmp,
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        let cmp = &mut self.cmp;
+        // sift_down builds a max heap, so reversed to get the smallest on top
+        let mut reversed = |a: &T, b: &T| cmp(b, a);
+
+        if self.heap.len() < self.k {
+            self.heap.push(value);
+
+            // Only becomes a heap once it's full, until then nothing gets replaced
+            if self.heap.len() == self.k {
+                for root in (0..self.k / 2).rev() {
+                    sift_down(&mut self.heap, root, self.k, &mut reversed);
+                }
+            }
+        } else if self.k > 0 && reversed(&value, &self.heap[0]) == Ordering::Less {
+            // Something equal to the smallest kept one doesn't replace it
+            self.heap[0] = value;
+            sift_down(&mut self.heap, 0, self.k, &mut reversed);
+        }
+    }
+
+    // The smallest of the ones kept, once there are k of them
+    pub fn threshold(&self) -> Option<&T> {
+        if self.k > 0 && self.heap.len() == self.k {
+            Some(&self.heap[0])
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    // Biggest first
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut cmp = self.cmp;
+        heap_sort_by(&mut self.heap, |a, b| cmp(b, a));
+        self.heap
+    }
+}
+
+impl<T, F> Extend<T> for TopK<T, F>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        assert_eq!(*select_nth(&mut arr, 3), 9);
+        assert_eq!(*select_nth_linear(&mut arr, 0), 3);
+        assert_eq!(*select_nth_linear(&mut arr, 6), 420);
+
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        partial_sort(&mut arr, 3);
+        assert_eq!(arr[..3], [3, 4, 7]);
+
+        assert_eq!(top_k([9, 3, 7, 4, 69, 420, 42], 3), [420, 69, 42]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index out of bounds")]
+    fn out_of_bounds() {
+        select_nth(&mut [1, 2, 3], 3);
+    }
+
+    fn inputs() -> Vec<Vec<u64>> {
+        let mut rng = XorShift::new(39);
+        let mut inputs = Vec::new();
+        for n in [1, 2, 5, 16, 17, 100, 1000] {
+            for modulo in [2, 10, 1_000_000] {
+                inputs.push((0..n).map(|_| rng.next_u64() % modulo).collect());
+            }
+            inputs.push((0..n).collect());
+            inputs.push((0..n).rev().collect());
+        }
+        inputs
+    }
+
+    fn check_selected(arr: &[u64], k: usize, expected: &[u64]) {
+        assert_eq!(arr[k], expected[k]);
+        assert!(arr[..k].iter().all(|&x| x <= arr[k]));
+        assert!(arr[k + 1..].iter().all(|&x| x >= arr[k]));
+    }
+
+    #[test]
+    fn select_against_sort() {
+        for original in inputs() {
+            let mut expected = original.clone();
+            expected.sort();
+
+            // Every k for the small ones, a handful for the rest
+            let step = original.len() / 50 + 1;
+            for k in (0..original.len())
+                .step_by(step)
+                .chain([original.len() - 1])
+            {
+                let mut arr = original.clone();
+                assert_eq!(*select_nth(&mut arr, k), expected[k]);
+                check_selected(&arr, k, &expected);
+
+                let mut arr = original.clone();
+                assert_eq!(*select_nth_linear(&mut arr, k), expected[k]);
+                check_selected(&arr, k, &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn median_of_medians_is_linear() {
+        let n = 100_000;
+        let mut rng = XorShift::new(40);
+        let random: Vec<u32> = (0..n).map(|_| rng.next_u64() as u32).collect();
+        let sorted: Vec<u32> = (0..n).collect();
+        let organ_pipe: Vec<u32> = (0..n / 2).chain((0..n / 2).rev()).collect();
+
+        for original in [random, sorted, organ_pipe] {
+            for k in [0, n as usize / 2, n as usize - 1] {
+                let mut comparisons = 0;
+                let mut arr = original.clone();
+                select_nth_linear_by(&mut arr, k, |a, b| {
+                    comparisons += 1;
+                    a.cmp(b)
+                });
+                assert!(comparisons < 30 * n as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn partial_sort_against_sort() {
+        for original in inputs() {
+            let mut expected = original.clone();
+            expected.sort();
+
+            for k in [0, 1, original.len() / 2, original.len(), original.len() + 5] {
+                let mut arr = original.clone();
+                partial_sort(&mut arr, k);
+
+                let k = k.min(arr.len());
+                assert_eq!(arr[..k], expected[..k]);
+                let mut rest = arr[k..].to_vec();
+                rest.sort();
+                assert_eq!(rest, expected[k..]);
+            }
+        }
+
+        let mut arr = ["bb", "a", "dddd", "ccc"];
+        partial_sort_by_key(&mut arr, 2, |s| s.len());
+        assert_eq!(arr[..2], ["a", "bb"]);
+    }
+
+    #[test]
+    fn top_k_against_sort() {
+        for original in inputs() {
+            let mut expected = original.clone();
+            expected.sort_by(|a, b| b.cmp(a));
+
+            for k in [0, 1, 3, original.len(), original.len() + 5] {
+                let top = top_k(original.iter().copied(), k);
+                assert_eq!(top, expected[..k.min(expected.len())]);
+            }
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let mut rng = XorShift::new(41);
+        let mut top = TopK::with_cmp(10, |a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0));
+        let mut seen = Vec::new();
+
+        // Fed in chunks, only 10 are ever held
+        for chunk in 0..100 {
+            let values: Vec<(u32, usize)> = (0..100)
+                .map(|i| (rng.range(0, 1000) as u32, chunk * 100 + i))
+                .collect();
+            seen.extend(values.iter().copied());
+            top.extend(values);
+            assert!(top.len() <= 10);
+        }
+        assert!(top.threshold().is_some());
+
+        // Which of the equal values make it in is up to the heap, so only keys are compared
+        let mut expected: Vec<u32> = seen.iter().map(|v| v.0).collect();
+        expected.sort_by(|a, b| b.cmp(a));
+        expected.truncate(10);
+        let result: Vec<u32> = top.into_sorted_vec().iter().map(|v| v.0).collect();
+        assert_eq!(result, expected);
+    }
+}
@@ -12,12 +12,12 @@ impl<T> Node<T> {
     }
 }
 
-pub struct Stack<T: Clone> {
+pub struct Stack<T> {
     head: Option<Rc<RefCell<Node<T>>>>,
     length: usize,
 }
 
-impl<T: Clone> Stack<T> {
+impl<T> Stack<T> {
     pub fn new() -> Self {
         Stack {
             head: None,
@@ -40,24 +40,105 @@ impl<T: Clone> Stack<T> {
     }
 
     pub fn pop(&mut self) -> Option<T> {
-        if self.head.is_none() {
-            return None;
-        }
-        self.length -= 1;
-
         self.head.take().map(|old_head| {
-            let next_head = old_head.borrow_mut().prev.clone();
+            self.length -= 1;
+            let next_head = old_head.borrow_mut().prev.take();
             self.head = next_head;
 
-            old_head.borrow().value.clone()
+            // Nothing points at the old head anymore, so the value is moved out instead of cloned
+            Rc::into_inner(old_head).unwrap().into_inner().value
         })
     }
 
+    // Keeps the top len values and drops everything below them. Only walks over the kept ones.
+    pub fn keep_top(&mut self, len: usize) {
+        if len >= self.length {
+            return;
+        }
+        if len == 0 {
+            self.clear();
+            return;
+        }
+
+        let mut node = Rc::clone(self.head.as_ref().unwrap());
+        for _ in 1..len {
+            let prev = Rc::clone(node.borrow().prev.as_ref().unwrap());
+            node = prev;
+        }
+
+        // Unlinked one by one for the same reason as in clear
+        let mut current = node.borrow_mut().prev.take();
+        while let Some(node) = current {
+            current = node.borrow_mut().prev.take();
+        }
+        self.length = len;
+    }
+
+    pub fn clear(&mut self) {
+        let mut current = self.head.take();
+        while let Some(node) = current {
+            current = node.borrow_mut().prev.take();
+        }
+        self.length = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl<T: Clone> Stack<T> {
     pub fn peek(&self) -> Option<T> {
         self.head.as_ref().map(|node| node.borrow().value.clone())
     }
 }
 
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Stack::new()
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        // Unlinking nodes one by one, otherwise dropping a long chain of Rc's recurses node by node
+        self.clear();
+    }
+}
+
+pub trait StackLike<T> {
+    fn push(&mut self, value: T);
+    fn pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<T>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Clone> StackLike<T> for Stack<T> {
+    fn push(&mut self, value: T) {
+        Stack::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        Stack::pop(self)
+    }
+
+    fn peek(&self) -> Option<T> {
+        Stack::peek(self)
+    }
+
+    fn len(&self) -> usize {
+        Stack::len(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::stack::Stack;
@@ -84,4 +165,68 @@ mod tests {
         assert_eq!(stack.peek(), None);
         assert_eq!(stack.pop(), None);
     }
+
+    #[test]
+    fn len() {
+        let mut stack: Stack<u32> = Stack::new();
+        assert!(stack.is_empty());
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.len(), 2);
+        stack.pop();
+        stack.pop();
+        stack.pop();
+        assert_eq!(stack.len(), 0);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let mut stack: Stack<u32> = Stack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.clear();
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn keep_top() {
+        let mut stack: Stack<u32> = Stack::new();
+        for i in 1..=5 {
+            stack.push(i);
+        }
+        stack.keep_top(9);
+        assert_eq!(stack.len(), 5);
+
+        stack.keep_top(2);
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.pop(), Some(5));
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), None);
+
+        stack.push(1);
+        stack.keep_top(0);
+        assert!(stack.is_empty());
+        assert_eq!(stack.peek(), None);
+    }
+
+    #[test]
+    fn values_without_clone() {
+        struct Token(String);
+
+        let mut stack = Stack::new();
+        stack.push(Token("a".to_string()));
+        stack.push(Token("b".to_string()));
+        assert_eq!(stack.pop().map(|t| t.0), Some("b".to_string()));
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn drop_long_stack() {
+        let mut stack: Stack<u32> = Stack::new();
+        for i in 0..1_000_000 {
+            stack.push(i);
+        }
+    }
 }
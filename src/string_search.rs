@@ -0,0 +1,480 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// All of these work on bytes and report every match, overlapping ones included, by the
+// byte offset where it starts. For a &str that's the same offset str::find gives, searching
+// for valid UTF-8 in valid UTF-8 can't match in the middle of a character. An empty pattern
+// matches everywhere, 0..=haystack.len(), like str::match_indices("").
+
+// Knuth-Morris-Pratt. After a mismatch the failure table says how much of the pattern is
+// still matched, so the haystack is read once, front to back. O(n + m).
+pub fn kmp<'a>(haystack: &'a [u8], pattern: &'a [u8]) -> Kmp<'a> {
+    Kmp {
+        haystack,
+        pattern,
+        failure: failure_table(pattern),
+        i: 0,
+        j: 0,
+    }
+}
+
+// failure[k] is the length of the longest proper prefix of pattern[..=k] that's also a
+// suffix of it
+pub fn failure_table(pattern: &[u8]) -> Vec<usize> {
+    let mut failure = vec![0; pattern.len()];
+    let mut k = 0;
+
+    for i in 1..pattern.len() {
+        while k > 0 && pattern[i] != pattern[k] {
+            k = failure[k - 1];
+        }
+        if pattern[i] == pattern[k] {
+            k += 1;
+        }
+        failure[i] = k;
+    }
+
+    failure
+}
+
+pub struct Kmp<'a> {
+    haystack: &'a [u8],
+    pattern: &'a [u8],
+    failure: Vec<usize>,
+    i: usize, // next byte of the haystack
+    j: usize, // how much of the pattern matches right before it
+}
+
+impl Iterator for Kmp<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let m = self.pattern.len();
+        if m == 0 {
+            return empty_match(&mut self.i, self.haystack.len());
+        }
+
+        while self.i < self.haystack.len() {
+            let b = self.haystack[self.i];
+            while self.j > 0 && b != self.pattern[self.j] {
+                self.j = self.failure[self.j - 1];
+            }
+            if b == self.pattern[self.j] {
+                self.j += 1;
+            }
+            self.i += 1;
+
+            if self.j == m {
+                // Carry on from the longest border, that's where overlapping matches start
+                self.j = self.failure[m - 1];
+                return Some(self.i - m);
+            }
+        }
+
+        None
+    }
+}
+
+// The empty pattern matches at every position, including the one past the end
+fn empty_match(pos: &mut usize, len: usize) -> Option<usize> {
+    if *pos > len {
+        return None;
+    }
+
+    *pos += 1;
+    Some(*pos - 1)
+}
+
+// Boyer-Moore-Horspool. Compares the whole window, then shifts it by how far the byte
+// under its last position is from the end of the pattern. Usually skips most of the
+// haystack on long patterns, O(n * m) in the worst case.
+pub fn horspool<'a>(haystack: &'a [u8], pattern: &'a [u8]) -> Horspool<'a> {
+    let m = pattern.len();
+    let mut shift = [m.max(1); 256];
+    if m > 0 {
+        for (k, &b) in pattern[..m - 1].iter().enumerate() {
+            shift[b as usize] = m - 1 - k;
+        }
+    }
+
+    Horspool {
+        haystack,
+        pattern,
+        shift,
+        pos: 0,
+    }
+}
+
+pub struct Horspool<'a> {
+    haystack: &'a [u8],
+    pattern: &'a [u8],
+    shift: [usize; 256],
+    pos: usize,
+}
+
+impl Iterator for Horspool<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let m = self.pattern.len();
+        if m == 0 {
+            return empty_match(&mut self.pos, self.haystack.len());
+        }
+
+        while self.pos + m <= self.haystack.len() {
+            let start = self.pos;
+            let window = &self.haystack[start..start + m];
+            // A smaller shift would put a different byte of the pattern under the last
+            // byte of the window, so no match (overlapping or not) gets skipped
+            self.pos += self.shift[window[m - 1] as usize];
+
+            if window == self.pattern {
+                return Some(start);
+            }
+        }
+
+        None
+    }
+}
+
+// z[i] is the length of the longest common prefix of s and s[i..], z[0] is s.len()
+pub fn z_array(s: &[u8]) -> Vec<usize> {
+    let n = s.len();
+    let mut z = vec![0; n];
+    if n == 0 {
+        return z;
+    }
+    z[0] = n;
+
+    // s[lo..hi] is the match that reaches furthest right so far
+    let (mut lo, mut hi) = (0, 0);
+    for i in 1..n {
+        if i < hi {
+            z[i] = z[i - lo].min(hi - i);
+        }
+        while i + z[i] < n && s[z[i]] == s[i + z[i]] {
+            z[i] += 1;
+        }
+        if i + z[i] > hi {
+            lo = i;
+            hi = i + z[i];
+        }
+    }
+
+    z
+}
+
+// Z algorithm on the pattern followed by the haystack. Anywhere in the haystack part
+// with a z value of at least the pattern length is a match. O(n + m) time and memory.
+pub fn z_search(haystack: &[u8], pattern: &[u8]) -> ZSearch {
+    let m = pattern.len();
+    let mut joined = Vec::with_capacity(m + haystack.len());
+    joined.extend_from_slice(pattern);
+    joined.extend_from_slice(haystack);
+
+    ZSearch {
+        z: z_array(&joined),
+        m,
+        pos: m,
+    }
+}
+
+pub struct ZSearch {
+    z: Vec<usize>,
+    m: usize,
+    pos: usize, // in the joined string
+}
+
+impl Iterator for ZSearch {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.m == 0 {
+            return empty_match(&mut self.pos, self.z.len());
+        }
+
+        while self.pos < self.z.len() {
+            let i = self.pos;
+            self.pos += 1;
+            if self.z[i] >= self.m {
+                return Some(i - self.m);
+            }
+        }
+
+        None
+    }
+}
+
+// Base of the rolling hash, arithmetic wraps around at 2^64. Collisions are possible,
+// every hit is checked byte by byte.
+const BASE: u64 = 1_000_003;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub pattern: usize, // index into the patterns
+    pub start: usize,
+}
+
+// Rabin-Karp for many patterns at once. There's a rolling hash per distinct pattern length,
+// and a map from hash to the patterns of that length, so every position costs one lookup
+// per length no matter how many patterns there are. Matches come out by position, and
+// by pattern index for the same position.
+pub fn rabin_karp<'a>(haystack: &'a [u8], patterns: &'a [&'a [u8]]) -> RabinKarp<'a> {
+    let mut groups: Vec<Group> = Vec::new();
+
+    for (idx, pattern) in patterns.iter().enumerate() {
+        let len = pattern.len();
+        let group = match groups.iter().position(|g| g.len == len) {
+            Some(g) => &mut groups[g],
+            None => {
+                groups.push(Group::new(haystack, len));
+                groups.last_mut().unwrap()
+            }
+        };
+        group.patterns.entry(hash(pattern)).or_default().push(idx);
+    }
+
+    RabinKarp {
+        haystack,
+        patterns,
+        groups,
+        pos: 0,
+        pending: Vec::new(),
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |h: u64, &b| h.wrapping_mul(BASE).wrapping_add(b as u64))
+}
+
+struct Group {
+    len: usize,
+    top: u64,  // BASE^(len - 1), for taking the oldest byte out of the hash
+    hash: u64, // of haystack[pos..pos + len]
+    patterns: HashMap<u64, Vec<usize>>,
+}
+
+impl Group {
+    fn new(haystack: &[u8], len: usize) -> Group {
+        let top = (1..len).fold(1, |p: u64, _| p.wrapping_mul(BASE));
+        let hash = if len <= haystack.len() {
+            self::hash(&haystack[..len])
+        } else {
+            0
+        };
+
+        Group {
+            len,
+            top,
+            hash,
+            patterns: HashMap::new(),
+        }
+    }
+}
+
+pub struct RabinKarp<'a> {
+    haystack: &'a [u8],
+    patterns: &'a [&'a [u8]],
+    groups: Vec<Group>,
+    pos: usize,
+    pending: Vec<Match>, // found at the last position, biggest pattern index first
+}
+
+impl Iterator for RabinKarp<'_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        let n = self.haystack.len();
+
+        loop {
+            if let Some(found) = self.pending.pop() {
+                return Some(found);
+            }
+            if self.pos > n {
+                return None;
+            }
+
+            let pos = self.pos;
+            for group in self.groups.iter_mut() {
+                let len = group.len;
+                if pos + len > n {
+                    continue;
+                }
+
+                if let Some(candidates) = group.patterns.get(&group.hash) {
+                    for &idx in candidates.iter() {
+                        if &self.haystack[pos..pos + len] == self.patterns[idx] {
+                            self.pending.push(Match {
+                                pattern: idx,
+                                start: pos,
+                            });
+                        }
+                    }
+                }
+
+                // Sliding the window one byte to the right
+                if len > 0 && pos + len < n {
+                    let out = self.haystack[pos] as u64;
+                    let new = self.haystack[pos + len] as u64;
+                    group.hash = group
+                        .hash
+                        .wrapping_sub(out.wrapping_mul(group.top))
+                        .wrapping_mul(BASE)
+                        .wrapping_add(new);
+                }
+            }
+
+            self.pending.sort_by_key(|found| Reverse(found.pattern));
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::XorShift;
+
+    fn brute_force(haystack: &[u8], pattern: &[u8]) -> Vec<usize> {
+        if pattern.len() > haystack.len() {
+            return vec![];
+        }
+        (0..=haystack.len() - pattern.len())
+            .filter(|&i| &haystack[i..i + pattern.len()] == pattern)
+            .collect()
+    }
+
+    // Every match by calling str::find again one byte past the last one
+    fn with_find(haystack: &str, pattern: &str) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut from = 0;
+        while from <= haystack.len() {
+            if !haystack.is_char_boundary(from) {
+                from += 1;
+                continue;
+            }
+            match haystack[from..].find(pattern) {
+                Some(i) => {
+                    found.push(from + i);
+                    from += i + 1;
+                }
+                None => break,
+            }
+        }
+        found
+    }
+
+    fn all(haystack: &str, pattern: &str) -> [Vec<usize>; 4] {
+        let (h, p) = (haystack.as_bytes(), pattern.as_bytes());
+        let patterns = [p];
+        [
+            kmp(h, p).collect(),
+            horspool(h, p).collect(),
+            z_search(h, p).collect(),
+            rabin_karp(h, &patterns).map(|m| m.start).collect(),
+        ]
+    }
+
+    #[test]
+    fn test() {
+        for found in all("abracadabra", "abra") {
+            assert_eq!(found, [0, 7]);
+        }
+        for found in all("aaaaa", "aa") {
+            assert_eq!(found, [0, 1, 2, 3]);
+        }
+        for found in all("abc", "") {
+            assert_eq!(found, [0, 1, 2, 3]);
+        }
+        for found in all("abc", "abcd") {
+            assert!(found.is_empty());
+        }
+
+        // Byte offsets, same as str::find
+        let text = "héllo wörld, wörld";
+        for found in all(text, "wörld") {
+            assert_eq!(found, [7, 15]);
+            assert_eq!(found[0], text.find("wörld").unwrap());
+        }
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(failure_table(b"abacabab"), [0, 0, 1, 0, 1, 2, 3, 2]);
+        assert_eq!(z_array(b"aabxaab"), [7, 1, 0, 0, 3, 1, 0]);
+    }
+
+    fn random(rng: &mut XorShift, alphabet: &str, len: usize) -> String {
+        let letters = alphabet.as_bytes();
+        (0..len)
+            .map(|_| letters[rng.range(0, letters.len())] as char)
+            .collect()
+    }
+
+    #[test]
+    fn against_str_find() {
+        let mut rng = XorShift::new(46);
+        for alphabet in ["ab", "abc", "abcdefghij"] {
+            for _ in 0..200 {
+                let haystack = random(&mut rng, alphabet, 60);
+                let from = rng.range(0, 55);
+                let to = from + rng.range(0, 6);
+                let patterns = [
+                    random(&mut rng, alphabet, 3),
+                    random(&mut rng, alphabet, 1),
+                    haystack[from..to].to_string(),
+                ];
+
+                for pattern in patterns.iter() {
+                    let expected = with_find(&haystack, pattern);
+                    assert_eq!(
+                        expected,
+                        brute_force(haystack.as_bytes(), pattern.as_bytes())
+                    );
+                    for found in all(&haystack, pattern) {
+                        assert_eq!(found, expected, "{} {}", haystack, pattern);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn many_patterns() {
+        let mut rng = XorShift::new(47);
+        let haystack: Vec<u8> = (0..2000).map(|_| b'a' + rng.range(0, 4) as u8).collect();
+        let owned: Vec<Vec<u8>> = (0..100)
+            .map(|_| {
+                let len = rng.range(1, 7);
+                (0..len).map(|_| b'a' + rng.range(0, 4) as u8).collect()
+            })
+            .collect();
+        let patterns: Vec<&[u8]> = owned.iter().map(|p| p.as_slice()).collect();
+
+        let mut expected = Vec::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            for start in brute_force(&haystack, pattern) {
+                expected.push(Match {
+                    pattern: idx,
+                    start,
+                });
+            }
+        }
+        expected.sort_by_key(|m| (m.start, m.pattern));
+
+        let found: Vec<Match> = rabin_karp(&haystack, &patterns).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn lazy() {
+        // Only as much of the haystack as needed is looked at
+        let haystack = b"needle".repeat(1000);
+        let mut found = kmp(&haystack, b"needle");
+        assert_eq!(found.next(), Some(0));
+        assert_eq!(found.next(), Some(6));
+        assert_eq!(horspool(&haystack, b"eed").nth(2), Some(13));
+    }
+}
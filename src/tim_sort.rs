@@ -0,0 +1,721 @@
+use crate::instrumented::{NoObserver, Observer, Tracked};
+use crate::merge_sort::find_run_tracked;
+use std::cmp::{self, Ordering};
+use std::ops::Range;
+
+// Arrays shorter than this are sorted with binary insertion sort alone
+const MIN_MERGE: usize = 64;
+// How many times in a row one run has to win before we start galloping
+const MIN_GALLOP: usize = 7;
+
+pub fn tim_sort<T: Ord + Clone>(arr: &mut [T]) {
+    tim_sort_by(arr, T::cmp);
+}
+
+pub fn tim_sort_by_key<T: Clone, K: Ord, F: FnMut(&T) -> K>(arr: &mut [T], mut key: F) {
+    tim_sort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+pub fn tim_sort_by<T: Clone, F>(arr: &mut [T], cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    tim_sort_tracked(Tracked::new(arr, &NoObserver), cmp);
+}
+
+pub fn tim_sort_tracked<T: Clone, F, O>(mut arr: Tracked<T, O>, mut cmp: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    let n = arr.len();
+    if n < 2 {
+        return;
+    }
+
+    if n < MIN_MERGE {
+        let run = find_run_tracked(&mut arr, &mut cmp);
+        binary_insertion_sort(&mut arr, run, &mut cmp);
+        return;
+    }
+
+    let mut state = State {
+        runs: Vec::new(),
+        buffer: Vec::new(),
+        min_gallop: MIN_GALLOP,
+    };
+    let min_run = min_run(n);
+
+    let mut lo = 0;
+    while lo < n {
+        let mut len = find_run_tracked(&mut arr.slice(lo..n), &mut cmp);
+
+        // Short natural runs get extended to min_run, so we don't end up merging lots of tiny ones
+        if len < min_run {
+            let forced = cmp::min(min_run, n - lo);
+            binary_insertion_sort(&mut arr.slice(lo..lo + forced), len, &mut cmp);
+            len = forced;
+        }
+
+        state.runs.push(Run { start: lo, len });
+        state.merge_collapse(&mut arr, &mut cmp);
+        lo += len;
+    }
+
+    state.merge_force_collapse(&mut arr, &mut cmp);
+}
+
+// Picks a run length in [MIN_MERGE / 2, MIN_MERGE] such that n / min_run is a power of two
+// or just below one, so the final merges are as balanced as they can be
+pub fn min_run(mut n: usize) -> usize {
+    let mut r = 0;
+    while n >= MIN_MERGE {
+        r |= n & 1;
+        n >>= 1;
+    }
+    n + r
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Run {
+    pub start: usize,
+    pub len: usize,
+}
+
+// Which two runs (by index of the first one) should be merged next to keep the stack in shape,
+// or None if it's fine as is. The invariants, for every three consecutive runs X, Y, Z on top:
+//   X > Y + Z and Y > Z
+// The original TimSort only checked the top three runs, which can leave the invariant broken
+// deeper in the stack (de Gouw et al., 2015). Also checking the run below X fixes that.
+pub fn next_merge(runs: &[Run]) -> Option<usize> {
+    if runs.len() < 2 {
+        return None;
+    }
+
+    let mut n = runs.len() - 2;
+    if (n > 0 && runs[n - 1].len <= runs[n].len + runs[n + 1].len)
+        || (n > 1 && runs[n - 2].len <= runs[n - 1].len + runs[n].len)
+    {
+        // Merging the smaller neighbour of the middle run
+        if runs[n - 1].len < runs[n + 1].len {
+            n -= 1;
+        }
+    } else if runs[n].len > runs[n + 1].len {
+        return None;
+    }
+
+    Some(n)
+}
+
+pub fn invariants_hold(runs: &[Run]) -> bool {
+    (0..runs.len()).all(|i| {
+        (i + 1 >= runs.len() || runs[i].len > runs[i + 1].len)
+            && (i + 2 >= runs.len() || runs[i].len > runs[i + 1].len + runs[i + 2].len)
+    })
+}
+
+struct State<T> {
+    runs: Vec<Run>,
+    buffer: Vec<T>,
+    min_gallop: usize,
+}
+
+impl<T: Clone> State<T> {
+    fn merge_collapse<F, O>(&mut self, arr: &mut Tracked<T, O>, cmp: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        O: Observer,
+    {
+        while let Some(n) = next_merge(&self.runs) {
+            self.merge_at(arr, n, cmp);
+        }
+
+        debug_assert!(invariants_hold(&self.runs));
+    }
+
+    fn merge_force_collapse<F, O>(&mut self, arr: &mut Tracked<T, O>, cmp: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        O: Observer,
+    {
+        while self.runs.len() > 1 {
+            let mut n = self.runs.len() - 2;
+            if n > 0 && self.runs[n - 1].len < self.runs[n + 1].len {
+                n -= 1;
+            }
+            self.merge_at(arr, n, cmp);
+        }
+    }
+
+    fn merge_at<F, O>(&mut self, arr: &mut Tracked<T, O>, i: usize, cmp: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        O: Observer,
+    {
+        let Run { start, len: len1 } = self.runs[i];
+        let len2 = self.runs[i + 1].len;
+        self.runs[i].len = len1 + len2;
+        self.runs.remove(i + 1);
+
+        let mut run = arr.slice(start..start + len1 + len2);
+
+        // Whatever in run1 is not bigger than the first of run2 is in place already
+        let skip = gallop_tracked((&run, len1), &run, 0..len1, 0, cmp, true);
+        let mut run = run.slice(skip..len1 + len2);
+        let len1 = len1 - skip;
+        if len1 == 0 {
+            return;
+        }
+
+        // Same for the end of run2 that's bigger than the last of run1
+        let len2 = gallop_tracked(
+            (&run, len1 - 1),
+            &run,
+            len1..run.len(),
+            len2 - 1,
+            cmp,
+            false,
+        );
+        let mut run = run.slice(0..len1 + len2);
+        if len2 == 0 {
+            return;
+        }
+
+        // Copying out whichever run is shorter
+        if len1 <= len2 {
+            self.merge_lo(&mut run, len1, cmp);
+        } else {
+            self.merge_hi(&mut run, len1, cmp);
+        }
+    }
+
+    // Merges left to right with run1 (arr[..len1]) copied to the buffer
+    fn merge_lo<F, O>(&mut self, arr: &mut Tracked<T, O>, len1: usize, cmp: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        O: Observer,
+    {
+        let mut buffer = arr.copy_out(0..len1, &mut self.buffer);
+
+        let end = arr.len();
+        let mut c1 = 0; // next in buffer
+        let mut c2 = len1; // next in run2
+        let mut dest = 0;
+        let mut min_gallop = self.min_gallop;
+
+        // Moving from run2 to dest just swaps, the slots in between only hold
+        // stale copies of what's in the buffer
+        'outer: loop {
+            let mut count1 = 0;
+            let mut count2 = 0;
+
+            // One at a time until a run keeps winning
+            loop {
+                if arr.compare_with(c2, &buffer, c1, cmp) == Ordering::Less {
+                    arr.swap(dest, c2);
+                    dest += 1;
+                    c2 += 1;
+                    count2 += 1;
+                    count1 = 0;
+                    if c2 == end {
+                        break 'outer;
+                    }
+                } else {
+                    arr.swap_with(dest, &mut buffer, c1);
+                    dest += 1;
+                    c1 += 1;
+                    count1 += 1;
+                    count2 = 0;
+                    if c1 == len1 {
+                        break 'outer;
+                    }
+                }
+
+                if count1 >= min_gallop || count2 >= min_gallop {
+                    break;
+                }
+            }
+
+            // Galloping: finding how far a run wins in one go and moving all of it
+            loop {
+                count1 = gallop_tracked((arr, c2), &buffer, c1..len1, 0, cmp, true);
+                for _ in 0..count1 {
+                    arr.swap_with(dest, &mut buffer, c1);
+                    dest += 1;
+                    c1 += 1;
+                }
+                if c1 == len1 {
+                    break 'outer;
+                }
+
+                arr.swap(dest, c2);
+                dest += 1;
+                c2 += 1;
+                if c2 == end {
+                    break 'outer;
+                }
+
+                count2 = gallop_tracked((&buffer, c1), arr, c2..end, 0, cmp, false);
+                for _ in 0..count2 {
+                    arr.swap(dest, c2);
+                    dest += 1;
+                    c2 += 1;
+                }
+                if c2 == end {
+                    break 'outer;
+                }
+
+                arr.swap_with(dest, &mut buffer, c1);
+                dest += 1;
+                c1 += 1;
+                if c1 == len1 {
+                    break 'outer;
+                }
+
+                // Galloping pays off, so it gets easier to start next time
+                min_gallop = min_gallop.saturating_sub(1);
+                if count1 < MIN_GALLOP && count2 < MIN_GALLOP {
+                    break;
+                }
+            }
+
+            // And harder once it stops paying off
+            min_gallop += 2;
+        }
+
+        self.min_gallop = cmp::max(min_gallop, 1);
+
+        // Leftovers from run2 are in place already, only the buffer needs to go back
+        while c1 < len1 {
+            arr.swap_with(dest, &mut buffer, c1);
+            dest += 1;
+            c1 += 1;
+        }
+    }
+
+    // Merges right to left with run2 (arr[len1..]) copied to the buffer
+    fn merge_hi<F, O>(&mut self, arr: &mut Tracked<T, O>, len1: usize, cmp: &mut F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+        O: Observer,
+    {
+        let mut buffer = arr.copy_out(len1..arr.len(), &mut self.buffer);
+
+        // Counting what's left instead of pointing at the last elements, so nothing goes below zero.
+        // The next free slot from the right is always arr[i1 + i2 - 1].
+        let mut i1 = len1; // run1 left in arr[..i1]
+        let mut i2 = buffer.len(); // run2 left in buffer[..i2]
+        let mut min_gallop = self.min_gallop;
+
+        'outer: loop {
+            let mut count1 = 0;
+            let mut count2 = 0;
+
+            loop {
+                // On ties run2 goes last, it came last
+                if buffer.compare_with(i2 - 1, arr, i1 - 1, cmp) == Ordering::Less {
+                    arr.swap(i1 + i2 - 1, i1 - 1);
+                    i1 -= 1;
+                    count1 += 1;
+                    count2 = 0;
+                    if i1 == 0 {
+                        break 'outer;
+                    }
+                } else {
+                    arr.swap_with(i1 + i2 - 1, &mut buffer, i2 - 1);
+                    i2 -= 1;
+                    count2 += 1;
+                    count1 = 0;
+                    if i2 == 0 {
+                        break 'outer;
+                    }
+                }
+
+                if count1 >= min_gallop || count2 >= min_gallop {
+                    break;
+                }
+            }
+
+            loop {
+                // Everything in run1 bigger than the last of the buffer
+                count1 = i1 - gallop_tracked((&buffer, i2 - 1), arr, 0..i1, i1 - 1, cmp, true);
+                for _ in 0..count1 {
+                    arr.swap(i1 + i2 - 1, i1 - 1);
+                    i1 -= 1;
+                }
+                if i1 == 0 {
+                    break 'outer;
+                }
+
+                arr.swap_with(i1 + i2 - 1, &mut buffer, i2 - 1);
+                i2 -= 1;
+                if i2 == 0 {
+                    break 'outer;
+                }
+
+                // Everything in the buffer not smaller than the last of run1
+                count2 = i2 - gallop_tracked((arr, i1 - 1), &buffer, 0..i2, i2 - 1, cmp, false);
+                for _ in 0..count2 {
+                    arr.swap_with(i1 + i2 - 1, &mut buffer, i2 - 1);
+                    i2 -= 1;
+                }
+                if i2 == 0 {
+                    break 'outer;
+                }
+
+                arr.swap(i1 + i2 - 1, i1 - 1);
+                i1 -= 1;
+                if i1 == 0 {
+                    break 'outer;
+                }
+
+                min_gallop = min_gallop.saturating_sub(1);
+                if count1 < MIN_GALLOP && count2 < MIN_GALLOP {
+                    break;
+                }
+            }
+
+            min_gallop += 2;
+        }
+
+        self.min_gallop = cmp::max(min_gallop, 1);
+
+        // Leftovers from run1 are in place already
+        while i2 > 0 {
+            arr.swap_with(i1 + i2 - 1, &mut buffer, i2 - 1);
+            i2 -= 1;
+        }
+    }
+}
+
+// How many elements at the start of the sorted `arr` go before `key`: the ones smaller than it,
+// plus the equal ones when `right` is set. Searches outwards from `hint` in growing steps
+// first, so it's cheap when the answer is close to the hint.
+pub fn gallop<T, F>(key: &T, arr: &[T], hint: usize, cmp: &mut F, right: bool) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    search(arr.len(), hint, right, |i| cmp(&arr[i], key))
+}
+
+// Same thing for arr[range], with the key at keys[key] and the hint relative to the range
+pub fn gallop_tracked<T, F, O>(
+    (keys, key): (&Tracked<T, O>, usize),
+    arr: &Tracked<T, O>,
+    range: Range<usize>,
+    hint: usize,
+    cmp: &mut F,
+    right: bool,
+) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    search(range.len(), hint, right, |i| {
+        arr.compare_with(range.start + i, keys, key, cmp)
+    })
+}
+
+// order(i) is how the i-th element compares to the key
+fn search<C>(len: usize, hint: usize, right: bool, mut order: C) -> usize
+where
+    C: FnMut(usize) -> Ordering,
+{
+    if len == 0 {
+        return 0;
+    }
+
+    let mut before = |i: usize| match order(i) {
+        Ordering::Less => true,
+        Ordering::Equal => right,
+        Ordering::Greater => false,
+    };
+
+    let (mut lo, mut hi);
+
+    if before(hint) {
+        let mut last = 0;
+        let mut ofs = 1;
+        while hint + ofs < len && before(hint + ofs) {
+            last = ofs;
+            ofs = ofs * 2 + 1;
+        }
+        lo = hint + last + 1;
+        hi = cmp::min(hint + ofs, len);
+    } else {
+        let mut last = 0;
+        let mut ofs = 1;
+        while ofs <= hint && !before(hint - ofs) {
+            last = ofs;
+            ofs = ofs * 2 + 1;
+        }
+        lo = if ofs <= hint { hint - ofs + 1 } else { 0 };
+        hi = hint - last;
+    }
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if before(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    lo
+}
+
+// Insertion sort where arr[..sorted] is already sorted, finding the spot with a binary search
+fn binary_insertion_sort<T, F, O>(arr: &mut Tracked<T, O>, sorted: usize, cmp: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    O: Observer,
+{
+    for i in cmp::max(sorted, 1)..arr.len() {
+        // After all equal elements, to keep it stable
+        let pos = gallop_tracked((arr, i), arr, 0..i, i / 2, cmp, true);
+        arr.rotate_right(pos, i + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge_sort::{merge_sort_with, MergeStrategy};
+    use crate::quick_sort::introsort;
+    use crate::rng::XorShift;
+    use std::time::Instant;
+
+    #[test]
+    fn test() {
+        let mut arr = [9, 3, 7, 4, 69, 420, 42];
+        tim_sort(&mut arr);
+        assert_eq!(arr, [3, 4, 7, 9, 42, 69, 420]);
+
+        let mut arr = ["ccc", "a", "bb"];
+        tim_sort_by_key(&mut arr, |s| s.len());
+        assert_eq!(arr, ["a", "bb", "ccc"]);
+
+        let mut arr: Vec<u8> = vec![];
+        tim_sort(&mut arr);
+    }
+
+    #[test]
+    fn min_runs() {
+        assert_eq!(min_run(10), 10);
+        assert_eq!(min_run(63), 63);
+        assert_eq!(min_run(64), 32);
+        assert_eq!(min_run(65), 33);
+        assert_eq!(min_run(2750), 43);
+        for n in 64..5000 {
+            assert!((32..=64).contains(&min_run(n)));
+        }
+    }
+
+    #[test]
+    fn galloping_search() {
+        let arr = [1, 2, 2, 2, 5, 8, 8, 13];
+        for key in 0..15 {
+            let left = arr.iter().filter(|&&x| x < key).count();
+            let right = arr.iter().filter(|&&x| x <= key).count();
+            for hint in 0..arr.len() {
+                assert_eq!(gallop(&key, &arr, hint, &mut i32::cmp, false), left);
+                assert_eq!(gallop(&key, &arr, hint, &mut i32::cmp, true), right);
+            }
+        }
+    }
+
+    fn push_and_collapse(runs: &mut Vec<Run>, len: usize, next: fn(&[Run]) -> Option<usize>) {
+        let start = runs.last().map_or(0, |r| r.start + r.len);
+        runs.push(Run { start, len });
+        while let Some(n) = next(runs) {
+            runs[n].len += runs[n + 1].len;
+            runs.remove(n + 1);
+        }
+    }
+
+    // What TimSort did before the fix: only the top three runs were looked at
+    fn original_next_merge(runs: &[Run]) -> Option<usize> {
+        if runs.len() < 2 {
+            return None;
+        }
+
+        let mut n = runs.len() - 2;
+        if n > 0 && runs[n - 1].len <= runs[n].len + runs[n + 1].len {
+            if runs[n - 1].len < runs[n + 1].len {
+                n -= 1;
+            }
+        } else if runs[n].len > runs[n + 1].len {
+            return None;
+        }
+
+        Some(n)
+    }
+
+    const BROKEN_RUNS: [usize; 5] = [120, 80, 25, 20, 30];
+
+    #[test]
+    fn run_stack_invariant_bug() {
+        // 25 and 20 get merged into 45, then 80 > 45 + 30 and 45 > 30 look fine,
+        // but 120 > 80 + 45 doesn't hold anymore and the original never checks it
+        let mut runs = Vec::new();
+        for len in BROKEN_RUNS {
+            push_and_collapse(&mut runs, len, original_next_merge);
+        }
+        let lens: Vec<usize> = runs.iter().map(|r| r.len).collect();
+        assert_eq!(lens, [120, 80, 45, 30]);
+        assert!(!invariants_hold(&runs));
+
+        let mut runs = Vec::new();
+        for len in BROKEN_RUNS {
+            push_and_collapse(&mut runs, len, next_merge);
+            assert!(invariants_hold(&runs));
+        }
+    }
+
+    #[test]
+    fn sorts_broken_runs_input() {
+        // Ascending runs of ten times those lengths, each starting below where the last one ended.
+        // They're all above min_run (43 for 2750) so they're pushed as they are.
+        let mut arr: Vec<u32> = Vec::new();
+        for (i, len) in BROKEN_RUNS.iter().enumerate() {
+            let base = 10_000 - 1000 * i as u32;
+            arr.extend(base..base + 10 * *len as u32);
+        }
+        assert_eq!(min_run(arr.len()), 43);
+
+        let mut expected = arr.clone();
+        expected.sort();
+        // The run stack is checked with debug_assert after every collapse
+        tim_sort(&mut arr);
+        assert_eq!(arr, expected);
+    }
+
+    #[test]
+    fn against_std() {
+        let mut rng = XorShift::new(35);
+        for n in [0, 1, 2, 63, 64, 65, 100, 1000, 5000] {
+            for modulo in [2, 10, 1_000_000] {
+                let original: Vec<u64> = (0..n).map(|_| rng.next_u64() % modulo).collect();
+                let mut expected = original.clone();
+                expected.sort();
+
+                let mut arr = original.clone();
+                tim_sort(&mut arr);
+                assert_eq!(arr, expected);
+            }
+        }
+    }
+
+    fn partially_sorted(rng: &mut XorShift, n: usize, runs: usize) -> Vec<u64> {
+        // Sorted runs of random lengths with a few elements out of place
+        let mut arr: Vec<u64> = Vec::with_capacity(n);
+        while arr.len() < n {
+            let len = rng.range(1, 2 * n / runs + 2).min(n - arr.len());
+            let mut run: Vec<u64> = (0..len).map(|_| rng.next_u64() % 100_000).collect();
+            run.sort();
+            if rng.range(0, 4) == 0 {
+                run.reverse();
+            }
+            arr.extend(run);
+        }
+        for _ in 0..n / 100 {
+            let (a, b) = (rng.range(0, n), rng.range(0, n));
+            arr.swap(a, b);
+        }
+        arr
+    }
+
+    #[test]
+    fn partially_sorted_against_std() {
+        let mut rng = XorShift::new(36);
+        for runs in [1, 3, 20, 200] {
+            let original = partially_sorted(&mut rng, 10_000, runs);
+            let mut expected = original.clone();
+            expected.sort();
+
+            let mut arr = original.clone();
+            tim_sort(&mut arr);
+            assert_eq!(arr, expected);
+        }
+    }
+
+    #[test]
+    fn galloping_on_interleaved_blocks() {
+        // Long blocks of each run win in turn, which is where galloping kicks in
+        let mut arr: Vec<u32> = Vec::new();
+        for block in 0..50 {
+            arr.extend(block * 200..block * 200 + 100);
+        }
+        for block in 0..50 {
+            arr.extend(block * 200 + 100..block * 200 + 200);
+        }
+
+        let mut comparisons = 0;
+        tim_sort_by(&mut arr, |a, b| {
+            comparisons += 1;
+            a.cmp(b)
+        });
+        assert_eq!(arr, (0..10_000).collect::<Vec<u32>>());
+        // Finding the two runs takes 10000 comparisons on its own. A plain merge would
+        // add another 10000, galloping gets away with a fraction of that.
+        assert!(comparisons < 12_000);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Record {
+        key: u32,
+        id: usize,
+    }
+
+    #[test]
+    fn stability() {
+        let mut rng = XorShift::new(37);
+        for n in [10, 64, 65, 1000, 10_000] {
+            let mut arr: Vec<Record> = (0..n)
+                .map(|id| Record {
+                    key: rng.range(0, 8) as u32,
+                    id,
+                })
+                .collect();
+            tim_sort_by_key(&mut arr, |r| r.key);
+
+            for w in arr.windows(2) {
+                assert!(w[0].key < w[1].key || (w[0].key == w[1].key && w[0].id < w[1].id));
+            }
+        }
+    }
+
+    // cargo test --release bench_ -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_partially_sorted() {
+        let mut rng = XorShift::new(38);
+        let n = 2_000_000;
+
+        for runs in [1, 16, 1000, n / 10] {
+            let original = partially_sorted(&mut rng, n, runs);
+            println!("{} runs:", runs);
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            tim_sort(&mut arr);
+            println!("  tim_sort:       {:?}", start.elapsed());
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            merge_sort_with(&mut arr, MergeStrategy::Natural, &mut Vec::new(), u64::cmp);
+            println!("  natural merge:  {:?}", start.elapsed());
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            merge_sort_with(&mut arr, MergeStrategy::TopDown, &mut Vec::new(), u64::cmp);
+            println!("  top-down merge: {:?}", start.elapsed());
+
+            let mut arr = original.clone();
+            let start = Instant::now();
+            introsort(&mut arr);
+            println!("  introsort:      {:?}", start.elapsed());
+        }
+    }
+}
@@ -1,25 +1,123 @@
-pub fn two_crystal_balls(breaks: &[bool]) -> i32 {
-    let jmp = (breaks.len() as f64).sqrt().floor() as i32;
-    let mut i: i32 = jmp;
+pub fn two_crystal_balls(breaks: &[bool]) -> Option<usize> {
+    let jmp = ((breaks.len() as f64).sqrt().floor() as usize).max(1);
+    let mut i = jmp;
 
-    while i < breaks.len() as i32 {
-        if breaks[i as usize] {
+    while i < breaks.len() {
+        if breaks[i] {
             break;
         }
 
         i += jmp;
     }
 
-    i = i - jmp;
+    i -= jmp;
 
-    while i < breaks.len() as i32 {
-        if breaks[i as usize] {
-            return i;
+    while i < breaks.len() {
+        if breaks[i] {
+            return Some(i);
         }
         i += 1;
     }
 
-    -1
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EggDrop {
+    pub floor: Option<usize>, // lowest floor the ball breaks from, None if it never does
+    pub probes: usize,
+    pub breaks: usize,
+}
+
+// covered(d, k) is how many floors can be told apart with k balls and d drops. The first
+// drop goes as high as the floors below it can still be handled with one ball and one drop
+// less, if it survives that's a drop less for the floors above:
+// covered(d, k) = covered(d - 1, k - 1) + 1 + covered(d - 1, k)
+// which adds up to C(d, 1) + C(d, 2) + ... + C(d, k). Saturates at usize::MAX.
+fn covered(drops: usize, balls: usize) -> usize {
+    let mut sum: u128 = 0;
+    let mut choose: u128 = 1; // C(drops, i)
+
+    for i in 1..=balls.min(drops) {
+        // Exact, C(d, i - 1) * (d - i + 1) is always a multiple of i
+        choose = choose * (drops - i + 1) as u128 / i as u128;
+        sum += choose;
+        if sum >= usize::MAX as u128 {
+            return usize::MAX;
+        }
+    }
+
+    sum as usize
+}
+
+// Fewest drops that are enough to find the floor in the worst case
+pub fn min_drops(floors: usize, balls: usize) -> usize {
+    if floors > 0 && balls == 0 {
+        panic!("Need at least one ball");
+    }
+
+    // One ball can only go up a floor at a time
+    if balls <= 1 {
+        return floors;
+    }
+
+    // More balls than it takes to binary search don't help
+    let balls = balls.min(usize::BITS as usize);
+
+    // covered only grows with more drops, and floors drops always do
+    let (mut lo, mut hi) = (0, floors);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if covered(mid, balls) >= floors {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
+// Finds the lowest of floors 0..floors that breaks(floor) is true for, with the fewest
+// drops in the worst case that `balls` balls allow. breaks has to be monotone: once a ball
+// breaks from a floor, it breaks from every floor above it too. Never drops more than
+// min_drops(floors, balls) times and never breaks more than `balls` balls.
+pub fn egg_drop<B>(floors: usize, balls: usize, mut breaks: B) -> EggDrop
+where
+    B: FnMut(usize) -> bool,
+{
+    let mut drops = min_drops(floors, balls);
+    let mut balls = balls.min(usize::BITS as usize);
+
+    let mut result = EggDrop {
+        floor: None,
+        probes: 0,
+        breaks: 0,
+    };
+
+    // The answer is in lo..=hi, where hi means "a floor known to break" or floors if none is
+    let mut lo = 0;
+    let mut hi = floors;
+
+    while lo < hi {
+        // With the last ball this is 0, one floor at a time from the bottom
+        let below = covered(drops - 1, balls - 1);
+        let floor = lo + below.min(hi - lo - 1);
+        result.probes += 1;
+        drops -= 1;
+
+        if breaks(floor) {
+            result.breaks += 1;
+            balls -= 1;
+            hi = floor;
+        } else {
+            lo = floor + 1;
+        }
+    }
+
+    if lo < floors {
+        result.floor = Some(lo);
+    }
+    result
 }
 
 #[cfg(test)]
@@ -28,15 +126,93 @@ mod tests {
 
     #[test]
     fn test() {
-        assert_eq!(two_crystal_balls(&[false, false, true, true]), 2);
-        assert_eq!(two_crystal_balls(&[false, false, false, true, true]), 3);
+        assert_eq!(two_crystal_balls(&[false, false, true, true]), Some(2));
+        assert_eq!(
+            two_crystal_balls(&[false, false, false, true, true]),
+            Some(3)
+        );
         assert_eq!(
             two_crystal_balls(&[false, true, true, true, true, true, true, true]),
-            1
+            Some(1)
         );
         assert_eq!(
             two_crystal_balls(&[false, false, false, false, false, false, false, true]),
-            7
+            Some(7)
         );
+        assert_eq!(two_crystal_balls(&[false, false, false]), None);
+        assert_eq!(two_crystal_balls(&[]), None);
+    }
+
+    #[test]
+    fn drops_needed() {
+        assert_eq!(min_drops(100, 2), 14);
+        assert_eq!(min_drops(36, 2), 8);
+        assert_eq!(min_drops(100, 1), 100);
+        assert_eq!(min_drops(1000, 3), 19);
+        assert_eq!(min_drops(1000, 100), 10);
+        assert_eq!(min_drops(0, 0), 0);
+        assert_eq!(min_drops(usize::MAX, 1000), 64);
+    }
+
+    #[test]
+    fn hundred_floors() {
+        let found = egg_drop(100, 2, |floor| floor >= 73);
+        assert_eq!(found.floor, Some(73));
+        assert!(found.probes <= 14);
+        assert!(found.breaks <= 2);
+
+        assert_eq!(egg_drop(100, 2, |_| false).floor, None);
+        assert_eq!(egg_drop(100, 2, |_| true).floor, Some(0));
+    }
+
+    #[test]
+    fn exhaustive() {
+        for floors in 0..80 {
+            for balls in 1..5 {
+                let limit = min_drops(floors, balls);
+                let mut worst = 0;
+
+                for answer in 0..=floors {
+                    let found = egg_drop(floors, balls, |floor| floor >= answer);
+                    let expected = if answer < floors { Some(answer) } else { None };
+
+                    assert_eq!(found.floor, expected, "{} {} {}", floors, balls, answer);
+                    assert!(found.probes <= limit);
+                    assert!(found.breaks <= balls);
+                    worst = worst.max(found.probes);
+                }
+
+                // The DP bound is tight, some floor really takes that many drops
+                assert_eq!(worst, limit, "{} {}", floors, balls);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_building() {
+        let found = egg_drop(usize::MAX, 64, |floor| floor >= 1 << 40);
+        assert_eq!(found.floor, Some(1 << 40));
+        assert!(found.probes <= 64);
+    }
+
+    #[test]
+    fn one_ball() {
+        // No table of every drop, one ball is just floors drops
+        assert_eq!(min_drops(usize::MAX, 1), usize::MAX);
+
+        let found = egg_drop(1 << 40, 1, |floor| floor >= 5);
+        assert_eq!(found.floor, Some(5));
+        assert_eq!(found.probes, 6);
+        assert_eq!(found.breaks, 1);
+
+        let found = egg_drop(1 << 40, 2, |floor| floor >= (1 << 40) - 3);
+        assert_eq!(found.floor, Some((1 << 40) - 3));
+        assert!(found.probes <= min_drops(1 << 40, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least one ball")]
+    fn no_balls() {
+        egg_drop(10, 0, |_| true);
     }
 }
@@ -0,0 +1,142 @@
+use crate::array_list::ArrayList;
+use crate::stack::StackLike;
+
+const INITIAL_CAP: usize = 16;
+
+pub struct VecStack<T: Clone + Default> {
+    list: ArrayList<T>,
+}
+
+impl<T: Clone + Default> VecStack<T> {
+    pub fn new() -> Self {
+        VecStack::with_capacity(INITIAL_CAP)
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        VecStack {
+            list: ArrayList::new(cap),
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.list.push(value);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.list.pop()
+    }
+
+    pub fn peek(&self) -> Option<T> {
+        self.list.last().cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.len() == 0
+    }
+}
+
+impl<T: Clone + Default> Default for VecStack<T> {
+    fn default() -> Self {
+        VecStack::new()
+    }
+}
+
+impl<T: Clone + Default> StackLike<T> for VecStack<T> {
+    fn push(&mut self, value: T) {
+        VecStack::push(self, value)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        VecStack::pop(self)
+    }
+
+    fn peek(&self) -> Option<T> {
+        VecStack::peek(self)
+    }
+
+    fn len(&self) -> usize {
+        VecStack::len(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stack::{Stack, StackLike};
+    use crate::vec_stack::VecStack;
+    use std::time::Instant;
+
+    #[test]
+    fn test_1() {
+        let mut stack: VecStack<u32> = VecStack::with_capacity(0);
+        assert_eq!(stack.peek(), None);
+        stack.push(1);
+        assert_eq!(stack.peek(), Some(1));
+        stack.push(2);
+        assert_eq!(stack.peek(), Some(2));
+        stack.push(3);
+        stack.push(4);
+        assert_eq!(stack.len(), 4);
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop(), Some(3));
+        assert_eq!(stack.pop(), Some(2));
+        assert_eq!(stack.peek(), Some(1));
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(stack.peek(), None);
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+    }
+
+    fn exercise<S: StackLike<u64>>(stack: &mut S, ops: u64) -> u64 {
+        // Pushing three and popping two per round so the stack keeps growing
+        let mut checksum = 0;
+        let mut i = 0;
+        while i < ops {
+            stack.push(i);
+            stack.push(i + 1);
+            stack.push(i + 2);
+            checksum += stack.pop().unwrap();
+            checksum += stack.peek().unwrap();
+            checksum += stack.pop().unwrap();
+            i += 5;
+        }
+
+        while let Some(value) = stack.pop() {
+            checksum += value;
+        }
+
+        checksum
+    }
+
+    #[test]
+    fn same_behaviour_as_stack() {
+        let mut linked: Stack<u64> = Stack::new();
+        let mut contiguous: VecStack<u64> = VecStack::new();
+        assert_eq!(
+            exercise(&mut linked, 10_000),
+            exercise(&mut contiguous, 10_000)
+        );
+    }
+
+    // cargo test --release bench_ -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_stack_vs_vec_stack() {
+        let ops = 10_000_000;
+
+        let start = Instant::now();
+        let linked = exercise(&mut Stack::new(), ops);
+        let linked_time = start.elapsed();
+
+        let start = Instant::now();
+        let contiguous = exercise(&mut VecStack::new(), ops);
+        let contiguous_time = start.elapsed();
+
+        assert_eq!(linked, contiguous);
+        println!("Stack:    {:?}", linked_time);
+        println!("VecStack: {:?}", contiguous_time);
+    }
+}